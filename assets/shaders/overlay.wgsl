@@ -0,0 +1,97 @@
+#import bevy_pbr::{
+    forward_io::{VertexOutput, FragmentOutput},
+    pbr_fragment::pbr_input_from_standard_material,
+    pbr_functions::{alpha_discard, apply_pbr_lighting, main_pass_post_lighting_processing},
+}
+
+struct OverlayExtension {
+    min: f32,
+    max: f32,
+    opacity: f32,
+    resolution: u32,
+    interpolate: u32,
+};
+@group(2) @binding(100) var<uniform> overlay: OverlayExtension;
+// Texture and sampler
+@group(2) @binding(101)
+var gradient_texture: texture_2d<f32>;
+
+@group(2) @binding(102)
+var gradient_sampler: sampler;
+
+// One texel per grid cell, the six faces stacked on top of each other
+@group(2) @binding(103)
+var data_texture: texture_2d<f32>;
+
+// Returns the face a direction passes through, in the order of `DIRECTIONS`
+fn face_of(direction: vec3<f32>) -> u32 {
+    let extent = abs(direction);
+    if extent.x >= extent.y && extent.x >= extent.z {
+        return select(3u, 0u, direction.x > 0.0);
+    }
+    if extent.y >= extent.z {
+        return select(4u, 1u, direction.y > 0.0);
+    }
+    return select(5u, 2u, direction.z > 0.0);
+}
+
+// Reads a cell, clamped to the face it lies on
+fn load_cell(face: u32, cell: vec2<i32>) -> f32 {
+    let last = i32(overlay.resolution) - 1;
+    let clamped = clamp(cell, vec2<i32>(0), vec2<i32>(last));
+    let row = i32(face * overlay.resolution) + clamped.y;
+    return textureLoad(data_texture, vec2<i32>(clamped.x, row), 0).r;
+}
+
+// Looks up the overlay in a direction from the planet center, like `PlanetGrid::sample`
+fn overlay_value(direction: vec3<f32>) -> f32 {
+    var directions = array<vec3<f32>, 6>(
+        vec3<f32>(1.0, 0.0, 0.0),
+        vec3<f32>(0.0, 1.0, 0.0),
+        vec3<f32>(0.0, 0.0, 1.0),
+        vec3<f32>(-1.0, 0.0, 0.0),
+        vec3<f32>(0.0, -1.0, 0.0),
+        vec3<f32>(0.0, 0.0, -1.0),
+    );
+    let face = face_of(direction);
+    let local_up = directions[face];
+    let axis_a = local_up.yzx;
+    let axis_b = cross(local_up, axis_a);
+    let point_on_unit_cube = direction / dot(direction, local_up);
+    let uv = vec2<f32>(dot(point_on_unit_cube, axis_a), dot(point_on_unit_cube, axis_b)) * 0.5 + 0.5;
+    let position = uv * f32(overlay.resolution);
+    if overlay.interpolate == 0u {
+        return load_cell(face, vec2<i32>(floor(position)));
+    }
+
+    // The values belong to the centers of the cells
+    let texel = position - 0.5;
+    let cell = vec2<i32>(floor(texel));
+    let t = fract(texel);
+    let bottom = mix(load_cell(face, cell), load_cell(face, cell + vec2<i32>(1, 0)), t.x);
+    let top = mix(load_cell(face, cell + vec2<i32>(0, 1)), load_cell(face, cell + vec2<i32>(1, 1)), t.x);
+    return mix(bottom, top, t.y);
+}
+
+@fragment
+fn fragment(input: VertexOutput, @builtin(front_facing) is_front: bool) -> FragmentOutput {
+    var pbr_input = pbr_input_from_standard_material(input, is_front);
+
+    if overlay.opacity > 0.0 {
+        // The planet sits at the origin
+        let value = overlay_value(normalize(input.world_position.xyz));
+        let u = clamp((value - overlay.min) / (overlay.max - overlay.min), 0.0, 1.0);
+        let color = textureSampleLevel(gradient_texture, gradient_sampler, vec2<f32>(u, 0.5), 0.0);
+        pbr_input.material.base_color = mix(
+            pbr_input.material.base_color,
+            vec4<f32>(color.rgb, 1.0),
+            overlay.opacity,
+        );
+    }
+    pbr_input.material.base_color = alpha_discard(pbr_input.material, pbr_input.material.base_color);
+
+    var output: FragmentOutput;
+    output.color = apply_pbr_lighting(pbr_input);
+    output.color = main_pass_post_lighting_processing(pbr_input, output.color);
+    return output;
+}
@@ -1,20 +1,84 @@
-use bevy::prelude::*;
+use bevy::{
+    input::mouse::{AccumulatedMouseScroll, MouseScrollUnit},
+    prelude::*,
+    window::PrimaryWindow,
+};
 use common::states::Screen;
 use leafwing_input_manager::{
     Actionlike, InputManagerBundle,
     plugin::InputManagerPlugin,
-    prelude::{ActionState, InputMap, MouseMove, MouseScrollAxis},
+    prelude::{
+        ActionState, GamepadControlAxis, GamepadStick, InputMap, MouseMove, MouseScrollAxis,
+        VirtualAxis, VirtualDPad, WithAxisProcessingPipelineExt, WithDualAxisProcessingPipelineExt,
+    },
+};
+use planet_generation::{
+    PlanetSurface, TerrainRayCast, direction_from_lat_lon, lat_lon_from_direction,
 };
 
+/// How far the camera may orbit towards the poles, in degrees. Focused on the surface, it may
+/// move right over them.
+const MAX_LATITUDE: f32 = 80.0;
+
+/// How far two fingers have to pinch together, in pixels, to zoom as far as one step of
+/// the mouse wheel.
+const PINCH_PER_ZOOM_STEP: f32 = 20.0;
+
+/// How far browsers and touchpads scroll per step of the mouse wheel, in pixels.
+const PIXELS_PER_SCROLL_LINE: f32 = 100.0;
+
+/// Sticks rest a little off center, ignore anything below this.
+const STICK_DEADZONE: f32 = 0.15;
+
 #[derive(Clone, Default, Reflect, Resource)]
 #[reflect(Resource)]
 pub struct CameraSettings {
+    /// How far one step of the mouse wheel zooms.
+    pub zoom_step: f32,
+    /// How far held keys and a fully tilted stick zoom per second.
     pub zoom_speed: f32,
-    pub zoom_min: f32,
+    /// Closest the camera gets to the ground or the ocean below it, as a fraction of the
+    /// planet radius.
+    pub min_altitude: f32,
+    /// Farthest the camera gets from the planet center, as a fraction of the planet radius.
     pub zoom_max: f32,
+    /// How far dragging with the mouse or a finger pans, in degrees per pixel.
+    pub drag_speed: f32,
+    /// How far held keys and a fully tilted stick pan per second, in degrees.
     pub pan_speed: f32,
+    /// How quickly the camera catches up with where it should be. Higher is snappier.
+    pub easing: f32,
+    /// Height above the surface the camera moves to when it focuses on a point, as a fraction
+    /// of the planet radius.
+    pub focus_altitude: f32,
+    /// How far the camera tilts towards the horizon right above the focused point, in degrees.
+    pub max_tilt: f32,
+    /// Height above the focused point below which the camera starts to tilt, as a fraction of
+    /// the planet radius.
+    pub tilt_altitude: f32,
+}
+
+/// What the camera looks at. Set this to zoom onto a point on the surface, or press the focus
+/// key to zoom onto whatever lies under the cursor.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Reflect, Resource)]
+#[reflect(Resource)]
+pub enum CameraFocus {
+    /// Orbits the planet and looks at its center.
+    #[default]
+    Orbit,
+    /// Looks at a point on the surface, given in degrees. Panning moves the point along with
+    /// the camera.
+    Surface { latitude: f32, longitude: f32 },
+    /// Follows an entity on the surface until the player pans away.
+    Entity(Entity),
 }
 
+/// Marks entities on the planet that the camera focuses on and follows when they are
+/// under the cursor, instead of the ground they stand on.
+#[derive(Component, Default, Reflect)]
+#[reflect(Component)]
+pub struct Focusable;
+
 #[derive(Component)]
 pub struct CameraPlugin(pub CameraSettings);
 
@@ -22,14 +86,27 @@ impl Plugin for CameraPlugin {
     fn build(&self, app: &mut App) {
         app.register_type::<CameraSettings>()
             .register_type::<CameraMovementIntentions>()
+            .register_type::<CameraFocus>()
+            .register_type::<Focusable>()
             .insert_resource(self.0.clone())
             .insert_resource(CameraMovementIntentions::default())
             .insert_resource(CameraPosition::default())
+            .init_resource::<CameraView>()
+            .init_resource::<CameraFocus>()
             .add_plugins(InputManagerPlugin::<CameraActions>::default())
             .add_systems(Startup, setup_camera)
             .add_systems(
                 Update,
-                (record_intentions, apply_intentions).run_if(in_state(Screen::Gameplay)),
+                (
+                    record_intentions,
+                    focus_under_cursor,
+                    follow_focus,
+                    apply_intentions,
+                    ease_view,
+                    place_camera,
+                )
+                    .chain()
+                    .run_if(in_state(Screen::Gameplay)),
             )
             .add_systems(OnExit(Screen::Gameplay), reset_camera);
     }
@@ -39,7 +116,27 @@ fn setup_camera(mut commands: Commands) {
     let input_map = InputMap::default()
         .with_axis(CameraActions::Zoom, MouseScrollAxis::Y)
         .with(CameraActions::PanActivate, MouseButton::Middle)
-        .with_dual_axis(CameraActions::Pan, MouseMove::default());
+        .with_dual_axis(CameraActions::Pan, MouseMove::default())
+        .with_axis(
+            CameraActions::ZoomSteadily,
+            VirtualAxis::new(KeyCode::KeyE, KeyCode::KeyQ),
+        )
+        .with_axis(
+            CameraActions::ZoomSteadily,
+            GamepadControlAxis::RIGHT_Y
+                .inverted()
+                .with_deadzone_symmetric(STICK_DEADZONE),
+        )
+        .with_dual_axis(CameraActions::PanSteadily, VirtualDPad::wasd())
+        .with_dual_axis(CameraActions::PanSteadily, VirtualDPad::arrow_keys())
+        .with_dual_axis(
+            CameraActions::PanSteadily,
+            GamepadStick::LEFT.with_circle_deadzone(STICK_DEADZONE),
+        )
+        .with(CameraActions::Focus, KeyCode::KeyF)
+        .with(CameraActions::Focus, GamepadButton::South)
+        .with(CameraActions::ReturnToOrbit, KeyCode::KeyR)
+        .with(CameraActions::ReturnToOrbit, GamepadButton::East);
 
     commands.spawn((
         Name::new("Camera"),
@@ -57,6 +154,9 @@ fn setup_camera(mut commands: Commands) {
 
 fn reset_camera(camera_entity: Single<Entity, With<Camera3d>>, mut commands: Commands) {
     commands.entity(camera_entity.into_inner()).insert(camera());
+    commands.insert_resource(CameraPosition::default());
+    commands.insert_resource(CameraView::default());
+    commands.insert_resource(CameraFocus::default());
 }
 
 fn camera() -> impl Bundle {
@@ -68,63 +168,281 @@ fn camera() -> impl Bundle {
 
 #[derive(Actionlike, Clone, Debug, Eq, Hash, PartialEq, Reflect)]
 enum CameraActions {
+    /// Zooms in steps, like the mouse wheel.
     #[actionlike(Axis)]
     Zoom,
+    /// Zooms for as long as it is held, like keys and sticks.
+    #[actionlike(Axis)]
+    ZoomSteadily,
     PanActivate,
+    /// Drags the planet along, like the mouse.
     #[actionlike(DualAxis)]
     Pan,
+    /// Pans for as long as it is held, like keys and sticks.
+    #[actionlike(DualAxis)]
+    PanSteadily,
+    /// Focuses on what lies under the cursor.
+    Focus,
+    ReturnToOrbit,
 }
 
+/// Turns the input of every device into the same amounts of panning and zooming.
+///
+/// Dragging and the mouse wheel move by how far the input moved, held keys and sticks by
+/// how long they are held. Touch isn't supported by the input manager, so gestures are read
+/// from [`Touches`] directly: one finger drags, two fingers drag and pinch.
 fn record_intentions(
+    time: Res<Time>,
+    settings: Res<CameraSettings>,
+    touches: Res<Touches>,
+    scroll: Res<AccumulatedMouseScroll>,
     mut intentions: ResMut<CameraMovementIntentions>,
     query: Single<&ActionState<CameraActions>>,
 ) {
     let action_state = query.into_inner();
+    let (mut drag, pinch) = touch_gestures(&touches);
 
-    let mouse_move = action_state.axis_pair(&CameraActions::Pan);
     if action_state.pressed(&CameraActions::PanActivate) {
-        intentions.pan = mouse_move;
+        drag += action_state.axis_pair(&CameraActions::Pan);
+    }
+    // Dragging pulls the planet along, steady panning moves the camera the other way.
+    let steady = action_state.axis_pair(&CameraActions::PanSteadily) * Vec2::new(-1.0, 1.0);
+    intentions.pan = drag * settings.drag_speed + steady * settings.pan_speed * time.delta_secs();
+
+    // Some platforms, like the web, scroll by pixels rather than by steps of the wheel.
+    let steps_per_scroll = match scroll.unit {
+        MouseScrollUnit::Line => 1.0,
+        MouseScrollUnit::Pixel => 1.0 / PIXELS_PER_SCROLL_LINE,
+    };
+    let steps =
+        action_state.value(&CameraActions::Zoom) * steps_per_scroll - pinch / PINCH_PER_ZOOM_STEP;
+    intentions.zoom = steps * settings.zoom_step
+        + action_state.value(&CameraActions::ZoomSteadily)
+            * settings.zoom_speed
+            * time.delta_secs();
+}
+
+/// How far the fingers on the screen dragged and spread apart this frame, in pixels.
+fn touch_gestures(touches: &Touches) -> (Vec2, f32) {
+    let mut fingers = touches.iter();
+    match (fingers.next(), fingers.next(), fingers.next()) {
+        (Some(finger), None, _) => (finger.delta(), 0.0),
+        (Some(a), Some(b), None) => {
+            let spread = a.position().distance(b.position())
+                - a.previous_position().distance(b.previous_position());
+            ((a.delta() + b.delta()) / 2.0, spread)
+        }
+        _ => (Vec2::ZERO, 0.0),
+    }
+}
+
+/// Focuses on the [`Focusable`] entity or the point on the surface under the cursor.
+///
+/// Without a cursor, like on a gamepad, it focuses on the middle of the screen.
+fn focus_under_cursor(
+    action_state: Single<&ActionState<CameraActions>>,
+    window: Single<&Window, With<PrimaryWindow>>,
+    camera: Single<(&Camera, &GlobalTransform), With<Camera3d>>,
+    focusables: Query<(), With<Focusable>>,
+    mut terrain: TerrainRayCast,
+    mut focus: ResMut<CameraFocus>,
+) {
+    if !action_state.just_pressed(&CameraActions::Focus) {
+        return;
+    }
+    let (camera, camera_transform) = camera.into_inner();
+    let cursor = window.cursor_position().unwrap_or(window.size() / 2.0);
+    let Ok(ray) = camera.viewport_to_world(camera_transform, cursor) else {
+        return;
+    };
+    let Some((entity, local_point)) = terrain.cast_ray(ray, |entity| focusables.contains(entity))
+    else {
+        return;
+    };
+
+    if focusables.contains(entity) {
+        focus.set_if_neq(CameraFocus::Entity(entity));
     } else {
-        intentions.pan = Vec2::ZERO;
+        let (latitude, longitude) = lat_lon_from_direction(local_point);
+        *focus = CameraFocus::Surface {
+            latitude,
+            longitude,
+        };
+    }
+}
+
+/// Moves the camera to a new [`CameraFocus`] and keeps it above a followed entity.
+///
+/// The camera remembers how far out it orbited before it focused on the surface, and
+/// returns there when it goes back to orbit.
+fn follow_focus(
+    settings: Res<CameraSettings>,
+    intentions: Res<CameraMovementIntentions>,
+    action_state: Single<&ActionState<CameraActions>>,
+    transforms: Query<&GlobalTransform>,
+    surface: PlanetSurface,
+    mut focus: ResMut<CameraFocus>,
+    mut position: ResMut<CameraPosition>,
+) {
+    if action_state.just_pressed(&CameraActions::ReturnToOrbit) {
+        focus.set_if_neq(CameraFocus::Orbit);
+    }
+    if let CameraFocus::Entity(entity) = *focus {
+        let target = transforms
+            .get(entity)
+            .map(|transform| transform.translation());
+        match target {
+            // Panning lets go of the entity, but stays close to it and keeps the zoom.
+            Ok(target) if intentions.pan != Vec2::ZERO => {
+                let (latitude, longitude) = lat_lon_from_direction(target);
+                *focus.bypass_change_detection() = CameraFocus::Surface {
+                    latitude,
+                    longitude,
+                };
+            }
+            Ok(target) => {
+                (position.latitude, position.longitude) = lat_lon_from_direction(target);
+            }
+            Err(_) => *focus = CameraFocus::Orbit,
+        }
+    }
+    if !focus.is_changed() {
+        return;
     }
 
-    intentions.zoom = action_state.value(&CameraActions::Zoom);
+    let (latitude, longitude) = match *focus {
+        CameraFocus::Orbit => {
+            if let Some(distance) = position.orbit_distance.take() {
+                position.distance = distance;
+            }
+            position.latitude = position.latitude.clamp(-MAX_LATITUDE, MAX_LATITUDE);
+            return;
+        }
+        CameraFocus::Surface {
+            latitude,
+            longitude,
+        } => (latitude, longitude),
+        CameraFocus::Entity(_) => (position.latitude, position.longitude),
+    };
+    if position.orbit_distance.is_none() {
+        position.orbit_distance = Some(position.distance);
+    }
+    position.latitude = latitude.clamp(-90.0, 90.0);
+    position.longitude = longitude;
+    let direction = direction_from_lat_lon(latitude, longitude);
+    position.distance =
+        ground_radius(&surface, direction) + surface.radius() * settings.focus_altitude;
 }
 
 fn apply_intentions(
-    time: Res<Time>,
     intentions: Res<CameraMovementIntentions>,
     settings: Res<CameraSettings>,
+    surface: PlanetSurface,
+    mut focus: ResMut<CameraFocus>,
     mut position: ResMut<CameraPosition>,
-    camera: Single<&mut Transform, With<Camera>>,
 ) {
-    position.distance += intentions.zoom * settings.zoom_speed * time.delta_secs();
-    position.distance = position
-        .distance
-        .clamp(settings.zoom_min, settings.zoom_max);
-    position.longitude += intentions.pan.x * settings.pan_speed * time.delta_secs();
+    position.distance += intentions.zoom;
+    position.longitude += intentions.pan.x;
     if position.longitude < -180. {
         position.longitude += 360.;
     } else if position.longitude > 180. {
         position.longitude -= 360.;
     }
-    position.latitude = (position.latitude
-        + intentions.pan.y * settings.pan_speed * time.delta_secs())
-    .clamp(-80., 80.);
+    let max_latitude = match *focus {
+        CameraFocus::Orbit => MAX_LATITUDE,
+        _ => 90.0,
+    };
+    position.latitude = (position.latitude + intentions.pan.y).clamp(-max_latitude, max_latitude);
+    // Keep the focused point under the camera without refocusing, which would reset the zoom.
+    if let CameraFocus::Surface {
+        latitude,
+        longitude,
+    } = focus.bypass_change_detection()
+    {
+        (*latitude, *longitude) = (position.latitude, position.longitude);
+    }
+
+    // Clamp after panning, so the camera never ends up inside a mountain it panned towards.
+    let direction = direction_from_lat_lon(position.latitude, position.longitude);
+    let closest = ground_radius(&surface, direction) + surface.radius() * settings.min_altitude;
+    let farthest = surface.radius() * settings.zoom_max;
+    position.distance = position.distance.min(farthest).max(closest);
+}
+
+/// Moves the [`CameraView`] a bit closer to the [`CameraPosition`] every frame.
+fn ease_view(
+    time: Res<Time>,
+    settings: Res<CameraSettings>,
+    focus: Res<CameraFocus>,
+    position: Res<CameraPosition>,
+    mut view: ResMut<CameraView>,
+) {
+    // Exponential smoothing doesn't depend on the frame rate.
+    let t = 1.0 - (-settings.easing * time.delta_secs()).exp();
+    let current = &mut view.position;
+    // Take the short way around the planet.
+    let longitude_change =
+        (position.longitude - current.longitude + 180.0).rem_euclid(360.0) - 180.0;
+    current.longitude =
+        (current.longitude + longitude_change * t + 180.0).rem_euclid(360.0) - 180.0;
+    current.latitude = current.latitude.lerp(position.latitude, t);
+    current.distance = current.distance.lerp(position.distance, t);
+    let focused = if *focus == CameraFocus::Orbit {
+        0.0
+    } else {
+        1.0
+    };
+    view.focus = view.focus.lerp(focused, t);
+}
+
+/// Places the camera above the point of the [`CameraView`].
+///
+/// In orbit the camera looks at the planet center. Focused, it looks at the ground right
+/// below it, and the closer it gets the more it tilts towards the horizon.
+fn place_camera(
+    settings: Res<CameraSettings>,
+    view: Res<CameraView>,
+    surface: PlanetSurface,
+    camera: Single<&mut Transform, With<Camera>>,
+) {
+    let up = direction_from_lat_lon(view.position.latitude, view.position.longitude);
+    // Turning the point a quarter towards the north pole keeps north well defined at the poles.
+    let north = direction_from_lat_lon(view.position.latitude + 90.0, view.position.longitude);
+    let target = surface.sample(up).position * view.focus;
+    let altitude = view.position.distance - target.length();
+    let tilt_altitude = surface.radius() * settings.tilt_altitude;
+    let closeness = 1.0 - (altitude / tilt_altitude).clamp(0.0, 1.0);
+    let tilt = settings.max_tilt.to_radians() * closeness * view.focus;
+    let (sin, cos) = tilt.sin_cos();
 
     let mut transform = camera.into_inner();
-    transform.translation = position.as_vec3();
-    transform.look_at(Vec3::ZERO, Vec3::Y);
+    let translation = target + (up * cos - north * sin) * altitude;
+    // A tilted camera no longer sits above the point it looks at, so it can still clip into
+    // the terrain it is looking over. Push it back out along the vertical.
+    let closest = ground_radius(&surface, translation) + surface.radius() * settings.min_altitude;
+    transform.translation = translation.normalize_or_zero() * translation.length().max(closest);
+    let camera_up = Vec3::Y.lerp(up * sin + north * cos, view.focus);
+    transform.look_at(target, camera_up);
+}
+
+/// Distance from the planet center to whatever the camera would hit in a direction: the
+/// terrain, or the ocean above it.
+fn ground_radius(surface: &PlanetSurface, direction: Vec3) -> f32 {
+    let terrain = surface.sample(direction).position.length();
+    terrain.max(surface.sea_level_radius())
 }
 
 #[derive(Default, Reflect, Resource)]
 #[reflect(Resource)]
 pub struct CameraMovementIntentions {
+    /// How far the camera zooms out this frame. Negative zooms in.
     pub zoom: f32,
+    /// How far the camera pans this frame, in degrees of longitude and latitude.
     pub pan: Vec2,
 }
 
-#[derive(Reflect, Resource)]
+/// Where the camera is headed. Input and the [`CameraFocus`] move it directly.
+#[derive(Clone, Copy, Reflect, Resource)]
 #[reflect(Resource)]
 struct CameraPosition {
     /// east to west
@@ -132,6 +450,8 @@ struct CameraPosition {
     /// north to south
     latitude: f32,
     distance: f32,
+    /// The distance to go back to once the camera stops focusing on the surface.
+    orbit_distance: Option<f32>,
 }
 
 impl Default for CameraPosition {
@@ -140,17 +460,26 @@ impl Default for CameraPosition {
             longitude: 0.,
             latitude: 0.,
             distance: 5.0,
+            orbit_distance: None,
         }
     }
 }
 
-impl CameraPosition {
-    fn as_vec3(&self) -> Vec3 {
-        let x =
-            self.distance * self.latitude.to_radians().cos() * self.longitude.to_radians().cos();
-        let y = self.distance * self.latitude.to_radians().sin();
-        let z =
-            self.distance * self.latitude.to_radians().cos() * self.longitude.to_radians().sin();
-        Vec3 { x, y, z }
+/// Where the camera currently is, on its way to the [`CameraPosition`].
+#[derive(Reflect, Resource)]
+#[reflect(Resource)]
+struct CameraView {
+    position: CameraPosition,
+    /// How far the camera has turned from the planet center to the focused point, from
+    /// `0.0` in orbit to `1.0`.
+    focus: f32,
+}
+
+impl Default for CameraView {
+    fn default() -> Self {
+        Self {
+            position: CameraPosition::default(),
+            focus: 0.0,
+        }
     }
 }
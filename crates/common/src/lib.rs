@@ -1,3 +1,4 @@
 pub mod components;
+pub mod schedules;
 pub mod states;
 pub mod system_sets;
@@ -0,0 +1,9 @@
+use bevy::ecs::schedule::ScheduleLabel;
+
+/// Advances the ecological simulation by one tick.
+///
+/// The schedule is run from [`FixedUpdate`](bevy::app::FixedUpdate) as often as the current simulation speed
+/// asks for, so everything in it is independent of the frame rate. Tests can step the
+/// simulation deterministically by running it directly with `world.run_schedule(Simulation)`.
+#[derive(ScheduleLabel, Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub struct Simulation;
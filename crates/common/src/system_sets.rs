@@ -12,3 +12,18 @@ pub enum AppSet {
     /// Do everything else (consider splitting this into further variants).
     Update,
 }
+
+/// High-level groupings of systems for the app in the [`Simulation`](crate::schedules::Simulation)
+/// schedule. When adding a new variant, make sure to order it in the `configure_sets` call
+/// in the simulation plugin.
+#[derive(SystemSet, Debug, Clone, Copy, Eq, PartialEq, Hash, PartialOrd, Ord)]
+pub enum SimulationSet {
+    /// Advance the simulation clock.
+    Clock,
+    /// Update the physical environment, such as climate.
+    Environment,
+    /// Update living things.
+    Ecology,
+    /// Run the facilities placed by the player.
+    Facilities,
+}
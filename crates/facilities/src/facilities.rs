@@ -0,0 +1,65 @@
+//! Facilities that orbital drops deliver to the planet surface.
+
+use bevy::prelude::*;
+use camera::Focusable;
+use orbital_drop::{DropLanded, Payload};
+use planet_generation::{GeothermalField, SurfaceSample};
+
+use crate::{
+    geothermal_plant::GeothermalPlant,
+    power::{self, PowerConsumer, PowerProducer, Powered},
+    seeder::{self, Seeder},
+};
+
+pub fn plugin(app: &mut App) {
+    app.register_type::<Facility>()
+        .register_type::<GeothermalPlant>()
+        .register_type::<Seeder>()
+        .add_plugins((power::plugin, seeder::plugin))
+        .add_observer(build_facility);
+}
+
+/// A facility standing on the planet surface.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct Facility {
+    pub site: SurfaceSample,
+}
+
+fn build_facility(
+    trigger: Trigger<DropLanded>,
+    geothermal_field: Res<GeothermalField>,
+    mut commands: Commands,
+) {
+    let landed = trigger.event();
+    let mut entity_commands = commands.entity(landed.entity);
+    entity_commands.insert((Facility { site: landed.site }, Focusable));
+    match landed.payload {
+        Payload::GeothermalPlant => {
+            let plant = GeothermalPlant {
+                heat: geothermal_field.heat(landed.site.direction),
+            };
+            debug!(
+                "Geothermal plant online, producing {:.1} MW",
+                plant.power_output()
+            );
+            entity_commands.insert((
+                Name::new("Geothermal Plant"),
+                PowerProducer {
+                    output: plant.power_output(),
+                },
+                plant,
+            ));
+        }
+        Payload::Seeder => {
+            entity_commands.insert((
+                Name::new("Seeder"),
+                Seeder::default(),
+                PowerConsumer {
+                    demand: Seeder::DEMAND,
+                },
+                Powered::default(),
+            ));
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use bevy::prelude::*;
+
+/// Heat below which a geothermal plant doesn't produce any power.
+const MIN_HEAT: f32 = 0.4;
+/// Power output of a plant sitting on the hottest possible spot, in megawatts.
+const MAX_OUTPUT: f32 = 100.0;
+
+/// A power plant that converts the heat of the [`GeothermalField`](planet_generation::GeothermalField)
+/// beneath it into power.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct GeothermalPlant {
+    /// Heat of the geothermal field at the plant's location.
+    pub heat: f32,
+}
+
+impl GeothermalPlant {
+    /// Returns the power output in megawatts.
+    /// Hot spots produce a lot of power, cold spots barely any, if any at all.
+    pub fn power_output(&self) -> f32 {
+        let usable_heat = ((self.heat - MIN_HEAT) / (1.0 - MIN_HEAT)).clamp(0.0, 1.0);
+        usable_heat * MAX_OUTPUT
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use planet_generation::{GeothermalField, direction_from_lat_lon};
+
+    use super::*;
+
+    #[test]
+    fn power_output_scales_with_heat() {
+        let output = |heat| GeothermalPlant { heat }.power_output();
+        assert_eq!(output(0.0), 0.0);
+        assert_eq!(output(MIN_HEAT), 0.0);
+        assert_eq!(output(1.0), MAX_OUTPUT);
+        assert_eq!(output(2.0), MAX_OUTPUT);
+        let mut previous = 0.0;
+        for step in 1..=10 {
+            let heat = MIN_HEAT + (1.0 - MIN_HEAT) * step as f32 / 10.0;
+            assert!(output(heat) > previous, "no more power at heat {heat}");
+            previous = output(heat);
+        }
+    }
+
+    #[test]
+    fn hotter_sites_produce_more_power() {
+        let field = GeothermalField::default();
+        let mut sites: Vec<f32> = (-80..=80)
+            .step_by(10)
+            .flat_map(|latitude| {
+                (-180..180)
+                    .step_by(15)
+                    .map(move |longitude| direction_from_lat_lon(latitude as f32, longitude as f32))
+            })
+            .map(|direction| field.heat(direction))
+            .collect();
+        sites.sort_by(f32::total_cmp);
+        let outputs: Vec<f32> = sites
+            .iter()
+            .map(|&heat| GeothermalPlant { heat }.power_output())
+            .collect();
+        assert!(outputs.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert!(
+            outputs.first() < outputs.last(),
+            "the field has no hot spots"
+        );
+    }
+
+    #[test]
+    fn tectonic_heat_powers_plants_on_cold_noise() {
+        // `GeothermalField::heat` adds the heat of plate boundaries and hot spots on top of
+        // the noise, so a plant on a boundary can work where the noise alone is too cold.
+        let noise_heat = 0.5 * MIN_HEAT;
+        let output = |tectonic_heat: f32| {
+            GeothermalPlant {
+                heat: noise_heat + tectonic_heat,
+            }
+            .power_output()
+        };
+        assert_eq!(output(0.0), 0.0);
+        let outputs = [0.0, 0.25, 0.5, 0.75].map(output);
+        assert!(outputs.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert!(output(0.5) > 0.0);
+        assert_eq!(output(1.0), MAX_OUTPUT);
+    }
+}
@@ -0,0 +1,10 @@
+mod facilities;
+mod geothermal_plant;
+mod power;
+pub mod power_network;
+mod seeder;
+
+pub use facilities::*;
+pub use geothermal_plant::GeothermalPlant;
+pub use power::{PowerConsumer, PowerGrid, PowerProducer, Powered};
+pub use seeder::Seeder;
@@ -0,0 +1,142 @@
+use bevy::prelude::*;
+use common::{schedules::Simulation, states::Screen, system_sets::SimulationSet};
+use planet_generation::{Overlay, OverlayGradient, PlanetSurface, RegisterOverlay};
+
+use crate::{
+    facilities::Facility,
+    power_network::{self, PowerNode},
+};
+
+/// Maximum distance along the surface between two linked facilities, in world units.
+const LINK_RANGE: f32 = 1.0;
+/// Number of segments used to draw a link along the planet surface.
+const LINK_SEGMENTS: usize = 12;
+const LINK_COLOR: Color = Color::srgb(1.0, 0.85, 0.2);
+
+pub(crate) fn plugin(app: &mut App) {
+    app.register_type::<PowerProducer>()
+        .register_type::<PowerConsumer>()
+        .register_type::<Powered>()
+        .init_resource::<PowerGrid>()
+        .register_overlay(
+            Overlay {
+                name: "Power Coverage",
+                key: Some(KeyCode::KeyC),
+                unit: "",
+                gradient: OverlayGradient::Stops(vec![
+                    (0.0, Color::srgb(0.1, 0.1, 0.15)),
+                    (1.0, LINK_COLOR),
+                ]),
+            },
+            sample_power_coverage,
+        )
+        .add_systems(
+            Simulation,
+            solve_power_grid.in_set(SimulationSet::Facilities),
+        )
+        .add_systems(Update, draw_power_links.run_if(in_state(Screen::Gameplay)));
+}
+
+/// A facility that feeds power into the grid.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct PowerProducer {
+    /// Power output in megawatts.
+    pub output: f32,
+}
+
+/// A facility that needs power from the grid to work.
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct PowerConsumer {
+    /// Power demand in megawatts.
+    pub demand: f32,
+}
+
+/// Whether a [`PowerConsumer`] currently receives the power it needs.
+/// Unpowered facilities stop working.
+#[derive(Component, Default, PartialEq, Reflect)]
+#[reflect(Component)]
+pub struct Powered(pub bool);
+
+/// The links between facilities found in the last simulation tick.
+#[derive(Resource, Default)]
+pub struct PowerGrid {
+    pub links: Vec<(Entity, Entity)>,
+}
+
+pub(crate) fn solve_power_grid(
+    surface: PlanetSurface,
+    facilities: Query<(
+        Entity,
+        &Facility,
+        Option<&PowerProducer>,
+        Option<&PowerConsumer>,
+    )>,
+    mut powered: Query<&mut Powered>,
+    mut grid: ResMut<PowerGrid>,
+) {
+    let (entities, nodes): (Vec<Entity>, Vec<PowerNode>) = facilities
+        .iter()
+        .map(|(entity, facility, producer, consumer)| {
+            let node = PowerNode {
+                direction: facility.site.direction,
+                supply: producer.map_or(0.0, |producer| producer.output),
+                demand: consumer.map_or(0.0, |consumer| consumer.demand),
+            };
+            (entity, node)
+        })
+        .unzip();
+
+    let solution = power_network::solve(&nodes, surface.radius(), LINK_RANGE);
+    grid.links = solution
+        .links
+        .iter()
+        .map(|&(a, b)| (entities[a], entities[b]))
+        .collect();
+    for (entity, is_powered) in entities.into_iter().zip(solution.powered) {
+        if let Ok(mut powered) = powered.get_mut(entity) {
+            powered.set_if_neq(Powered(is_powered));
+        }
+    }
+}
+
+fn draw_power_links(grid: Res<PowerGrid>, facilities: Query<&Facility>, mut gizmos: Gizmos) {
+    for &(a, b) in &grid.links {
+        let Ok([a, b]) = facilities.get_many([a, b]) else {
+            continue;
+        };
+        let (from, to) = (a.site.position, b.site.position);
+        let height = from.length().max(to.length());
+        let points = (0..=LINK_SEGMENTS).map(|segment| {
+            let t = segment as f32 / LINK_SEGMENTS as f32;
+            from.lerp(to, t).normalize() * height
+        });
+        gizmos.linestrip(points, LINK_COLOR);
+    }
+}
+
+/// How well a facility built in each direction could link to the grid, from `0.0` out of
+/// range of every facility to `1.0` right next to one.
+fn sample_power_coverage(
+    In(directions): In<Vec<Vec3>>,
+    surface: PlanetSurface,
+    facilities: Query<&Facility>,
+) -> Vec<f32> {
+    directions
+        .into_iter()
+        .map(|direction| {
+            facilities
+                .iter()
+                .map(|facility| {
+                    let distance = power_network::great_circle_distance(
+                        direction,
+                        facility.site.direction,
+                        surface.radius(),
+                    );
+                    1.0 - distance / LINK_RANGE
+                })
+                .fold(0.0, f32::max)
+        })
+        .collect()
+}
@@ -0,0 +1,225 @@
+//! A headless solver for the power grid. It knows nothing about entities, which
+//! keeps it easy to test.
+
+use bevy::math::Vec3;
+
+/// A facility as seen by the power grid.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PowerNode {
+    /// Direction from the planet center towards the facility.
+    pub direction: Vec3,
+    /// Power fed into the grid, in megawatts.
+    pub supply: f32,
+    /// Power drawn from the grid, in megawatts.
+    pub demand: f32,
+}
+
+/// The result of [`solve`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PowerSolution {
+    /// Pairs of node indices that are connected by a surface link.
+    pub links: Vec<(usize, usize)>,
+    /// Index into [`PowerSolution::grids`] for every node.
+    pub grid_of_node: Vec<usize>,
+    /// Whether a node's demand is met. Nodes without demand are always powered.
+    pub powered: Vec<bool>,
+    /// Every connected group of nodes.
+    pub grids: Vec<GridBalance>,
+}
+
+/// Supply and demand of a group of connected nodes.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct GridBalance {
+    pub supply: f32,
+    pub demand: f32,
+    /// The part of the demand that is actually served.
+    pub served: f32,
+}
+
+/// Distance between two directions along the surface of a sphere with the given radius.
+pub fn great_circle_distance(a: Vec3, b: Vec3, radius: f32) -> f32 {
+    let cos_angle = a.normalize().dot(b.normalize()).clamp(-1.0, 1.0);
+    cos_angle.acos() * radius
+}
+
+/// Connects all nodes that are at most `link_range` apart along the surface and balances
+/// supply and demand within every connected grid.
+///
+/// If a grid doesn't produce enough power, consumers are served from the smallest demand
+/// upwards, so as many facilities as possible keep working. Ties are broken by node order,
+/// which keeps the result deterministic.
+pub fn solve(nodes: &[PowerNode], radius: f32, link_range: f32) -> PowerSolution {
+    let mut links = Vec::new();
+    let mut components = DisjointSet::new(nodes.len());
+    for (a, node_a) in nodes.iter().enumerate() {
+        for (b, node_b) in nodes.iter().enumerate().skip(a + 1) {
+            if great_circle_distance(node_a.direction, node_b.direction, radius) <= link_range {
+                links.push((a, b));
+                components.union(a, b);
+            }
+        }
+    }
+
+    let mut grid_of_root = vec![usize::MAX; nodes.len()];
+    let mut grid_of_node = Vec::with_capacity(nodes.len());
+    let mut grids: Vec<GridBalance> = Vec::new();
+    for (index, node) in nodes.iter().enumerate() {
+        let root = components.find(index);
+        if grid_of_root[root] == usize::MAX {
+            grid_of_root[root] = grids.len();
+            grids.push(GridBalance::default());
+        }
+        let grid = grid_of_root[root];
+        grids[grid].supply += node.supply;
+        grids[grid].demand += node.demand;
+        grid_of_node.push(grid);
+    }
+
+    let mut consumers: Vec<usize> = (0..nodes.len())
+        .filter(|&i| nodes[i].demand > 0.0)
+        .collect();
+    consumers.sort_by(|&a, &b| nodes[a].demand.total_cmp(&nodes[b].demand));
+    let mut powered = vec![true; nodes.len()];
+    for index in consumers {
+        let grid = &mut grids[grid_of_node[index]];
+        let demand = nodes[index].demand;
+        if grid.served + demand <= grid.supply {
+            grid.served += demand;
+        } else {
+            powered[index] = false;
+        }
+    }
+
+    PowerSolution {
+        links,
+        grid_of_node,
+        powered,
+        grids,
+    }
+}
+
+struct DisjointSet {
+    parents: Vec<usize>,
+}
+
+impl DisjointSet {
+    fn new(len: usize) -> Self {
+        Self {
+            parents: (0..len).collect(),
+        }
+    }
+
+    fn find(&mut self, index: usize) -> usize {
+        let mut root = index;
+        while self.parents[root] != root {
+            root = self.parents[root];
+        }
+        let mut current = index;
+        while self.parents[current] != root {
+            current = std::mem::replace(&mut self.parents[current], root);
+        }
+        root
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (root_a, root_b) = (self.find(a), self.find(b));
+        if root_a != root_b {
+            self.parents[root_b.max(root_a)] = root_a.min(root_b);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::{FRAC_PI_2, PI};
+
+    use super::*;
+
+    fn producer(direction: Vec3, supply: f32) -> PowerNode {
+        PowerNode {
+            direction,
+            supply,
+            demand: 0.0,
+        }
+    }
+
+    fn consumer(direction: Vec3, demand: f32) -> PowerNode {
+        PowerNode {
+            direction,
+            supply: 0.0,
+            demand,
+        }
+    }
+
+    #[test]
+    fn great_circle_distance_follows_the_surface() {
+        assert!((great_circle_distance(Vec3::X, Vec3::Y, 2.0) - FRAC_PI_2 * 2.0).abs() < 1e-5);
+        assert!((great_circle_distance(Vec3::X, Vec3::NEG_X, 1.0) - PI).abs() < 1e-5);
+        assert_eq!(great_circle_distance(Vec3::Z, Vec3::Z * 5.0, 3.0), 0.0);
+    }
+
+    #[test]
+    fn links_respect_the_range_limit() {
+        let near = Vec3::new(1.0, 0.1, 0.0);
+        let nodes = [
+            producer(Vec3::X, 10.0),
+            consumer(near, 5.0),
+            consumer(Vec3::NEG_X, 5.0),
+        ];
+        let solution = solve(&nodes, 1.0, 0.5);
+        assert_eq!(solution.links, vec![(0, 1)]);
+        assert_eq!(solution.grids.len(), 2);
+        assert_eq!(solution.grid_of_node[0], solution.grid_of_node[1]);
+        assert_ne!(solution.grid_of_node[0], solution.grid_of_node[2]);
+        assert_eq!(solution.powered, vec![true, true, false]);
+    }
+
+    #[test]
+    fn range_scales_with_the_planet_radius() {
+        let nodes = [producer(Vec3::X, 10.0), consumer(Vec3::Y, 5.0)];
+        assert!(solve(&nodes, 1.0, 2.0).powered[1]);
+        assert!(!solve(&nodes, 2.0, 2.0).powered[1]);
+    }
+
+    #[test]
+    fn power_is_relayed_through_other_facilities() {
+        let step = |angle: f32| Vec3::new(angle.cos(), angle.sin(), 0.0);
+        let nodes = [
+            producer(step(0.0), 10.0),
+            consumer(step(0.3), 2.0),
+            consumer(step(0.6), 2.0),
+        ];
+        let solution = solve(&nodes, 1.0, 0.35);
+        assert_eq!(solution.links, vec![(0, 1), (1, 2)]);
+        assert_eq!(solution.grids.len(), 1);
+        assert_eq!(solution.powered, vec![true, true, true]);
+    }
+
+    #[test]
+    fn shortage_serves_smallest_demand_first() {
+        let nodes = [
+            consumer(Vec3::X, 8.0),
+            producer(Vec3::X, 10.0),
+            consumer(Vec3::X, 3.0),
+            consumer(Vec3::X, 6.0),
+        ];
+        let solution = solve(&nodes, 1.0, 1.0);
+        assert_eq!(solution.powered, vec![false, true, true, true]);
+        assert_eq!(
+            solution.grids,
+            vec![GridBalance {
+                supply: 10.0,
+                demand: 17.0,
+                served: 9.0,
+            }]
+        );
+    }
+
+    #[test]
+    fn grid_without_producers_is_unpowered() {
+        let nodes = [consumer(Vec3::X, 1.0), consumer(Vec3::X, 1.0)];
+        let solution = solve(&nodes, 1.0, 1.0);
+        assert_eq!(solution.powered, vec![false, false]);
+        assert_eq!(solution.grids[0].served, 0.0);
+    }
+}
@@ -0,0 +1,68 @@
+use bevy::prelude::*;
+use common::{schedules::Simulation, system_sets::SimulationSet};
+
+use crate::power::{Powered, solve_power_grid};
+
+/// How much the planted radius grows per simulation tick while the seeder is powered.
+const SPREAD_PER_TICK: f32 = 0.005;
+
+pub(crate) fn plugin(app: &mut App) {
+    app.add_systems(
+        Simulation,
+        spread_plants
+            .after(solve_power_grid)
+            .in_set(SimulationSet::Facilities),
+    );
+}
+
+/// A facility that spreads plant life into its surroundings.
+/// It needs power from the grid to work.
+#[derive(Component, Default, Reflect)]
+#[reflect(Component)]
+pub struct Seeder {
+    /// Radius of the area planted so far, in world units.
+    pub planted_radius: f32,
+}
+
+impl Seeder {
+    /// Power a seeder draws from the grid, in megawatts.
+    pub const DEMAND: f32 = 30.0;
+    /// Radius beyond which a seeder can't plant anything.
+    pub const MAX_RADIUS: f32 = 0.5;
+}
+
+fn spread_plants(mut seeders: Query<(&mut Seeder, &Powered)>) {
+    for (mut seeder, powered) in &mut seeders {
+        if *powered == Powered(true) {
+            seeder.planted_radius =
+                (seeder.planted_radius + SPREAD_PER_TICK).min(Seeder::MAX_RADIUS);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+
+    fn spread_for(ticks: u32, powered: bool) -> f32 {
+        let mut world = World::new();
+        let seeder = world.spawn((Seeder::default(), Powered(powered))).id();
+        for _ in 0..ticks {
+            world.run_system_once(spread_plants).unwrap();
+        }
+        world.get::<Seeder>(seeder).unwrap().planted_radius
+    }
+
+    #[test]
+    fn unpowered_seeders_do_nothing() {
+        assert_eq!(spread_for(10, false), 0.0);
+    }
+
+    #[test]
+    fn powered_seeders_spread_up_to_their_range() {
+        assert!(spread_for(10, true) > 0.0);
+        assert_eq!(spread_for(1000, true), Seeder::MAX_RADIUS);
+    }
+}
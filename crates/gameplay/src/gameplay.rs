@@ -1,5 +1,11 @@
 use bevy::prelude::*;
 
 pub fn plugin(app: &mut App) {
-    app.add_plugins((tutorial::plugin, planet_generation::plugin));
+    app.add_plugins((
+        tutorial::plugin,
+        planet_generation::plugin,
+        orbital_drop::plugin,
+        facilities::plugin,
+        simulation::plugin,
+    ));
 }
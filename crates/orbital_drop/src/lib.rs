@@ -0,0 +1,3 @@
+mod orbital_drop;
+
+pub use orbital_drop::*;
@@ -0,0 +1,320 @@
+//! Orbital drops that are aimed at the planet with the cursor and land on its surface.
+
+use bevy::{prelude::*, window::PrimaryWindow};
+use common::{states::Screen, system_sets::AppSet};
+use leafwing_input_manager::prelude::*;
+use planet_generation::{Planet, PlanetSurface, SurfaceSample, TerrainRayCast};
+
+/// Distance from the planet center at which drops start, in planet radii.
+const ORBIT_ALTITUDE: f32 = 3.0;
+const DESCENT_DURATION_SECS: f32 = 2.5;
+const SINK_DURATION_SECS: f32 = 3.0;
+/// How far into the seabed a sinking drop ends up, in world units.
+const SINK_DEPTH: f32 = 0.1;
+const DROP_SIZE: f32 = 0.04;
+
+pub fn plugin(app: &mut App) {
+    app.register_type::<OrbitalDrop>()
+        .register_type::<DropTargeting>()
+        .add_plugins(InputManagerPlugin::<OrbitalDropActions>::default())
+        .add_systems(OnEnter(Screen::Gameplay), setup)
+        .add_observer(spawn_landing_marker)
+        .add_systems(
+            Update,
+            (
+                check.in_set(AppSet::RecordInput),
+                (update_target, deploy, descend, sink)
+                    .chain()
+                    .in_set(AppSet::Update),
+            )
+                .run_if(in_state(Screen::Gameplay)),
+        );
+}
+
+#[derive(Actionlike, Clone, Debug, Eq, Hash, PartialEq, Reflect)]
+enum OrbitalDropActions {
+    Arm,
+    CyclePayload,
+    Confirm,
+    Cancel,
+}
+
+/// What an [`OrbitalDrop`] delivers to the surface.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Reflect)]
+pub enum Payload {
+    #[default]
+    GeothermalPlant,
+    Seeder,
+}
+
+impl Payload {
+    fn next(self) -> Self {
+        match self {
+            Payload::GeothermalPlant => Payload::Seeder,
+            Payload::Seeder => Payload::GeothermalPlant,
+        }
+    }
+}
+
+/// Whether the player is currently aiming an orbital drop, and where it would land.
+#[derive(Component, Default, Reflect)]
+#[reflect(Component)]
+pub struct DropTargeting {
+    pub armed: bool,
+    pub target: Option<SurfaceSample>,
+    pub payload: Payload,
+}
+
+/// A drop that has been deployed from orbit. It is spawned as a child of the [`Planet`].
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct OrbitalDrop {
+    pub site: SurfaceSample,
+    pub payload: Payload,
+}
+
+/// Marks an [`OrbitalDrop`] that has touched down on land.
+#[derive(Component)]
+pub struct Landed;
+
+#[derive(Component)]
+struct Descending {
+    elapsed: f32,
+}
+
+#[derive(Component)]
+struct Sinking {
+    elapsed: f32,
+    /// Where the drop hit the ocean surface.
+    from: Vec3,
+}
+
+#[derive(Component)]
+struct LandingMarker;
+
+#[derive(Resource)]
+struct DropAssets {
+    drop_mesh: Handle<Mesh>,
+    drop_material: Handle<StandardMaterial>,
+    land_material: Handle<StandardMaterial>,
+    water_material: Handle<StandardMaterial>,
+}
+
+/// Triggered when an [`OrbitalDrop`] lands on solid ground.
+#[derive(Event)]
+pub struct DropLanded {
+    pub entity: Entity,
+    pub site: SurfaceSample,
+    pub payload: Payload,
+}
+
+/// Triggered when an [`OrbitalDrop`] came down in the ocean and sank.
+#[derive(Event)]
+pub struct DropLost {
+    pub site: SurfaceSample,
+}
+
+fn setup(
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut commands: Commands,
+) {
+    let input_map = InputMap::default()
+        .with(OrbitalDropActions::Arm, KeyCode::KeyO)
+        .with(OrbitalDropActions::CyclePayload, KeyCode::Tab)
+        .with(OrbitalDropActions::Confirm, MouseButton::Left)
+        .with(OrbitalDropActions::Cancel, MouseButton::Right)
+        .with(OrbitalDropActions::Cancel, KeyCode::Escape);
+    commands.spawn((
+        Name::new("OrbitalDropControls"),
+        InputManagerBundle::with_map(input_map),
+        DropTargeting::default(),
+        StateScoped(Screen::Gameplay),
+    ));
+
+    let assets = DropAssets {
+        drop_mesh: meshes.add(Capsule3d::new(DROP_SIZE * 0.5, DROP_SIZE)),
+        drop_material: materials.add(Color::srgb(0.8, 0.8, 0.85)),
+        land_material: materials.add(StandardMaterial {
+            base_color: Color::srgba(0.2, 0.9, 0.3, 0.8),
+            alpha_mode: AlphaMode::Blend,
+            unlit: true,
+            ..default()
+        }),
+        water_material: materials.add(StandardMaterial {
+            base_color: Color::srgba(0.9, 0.2, 0.2, 0.8),
+            alpha_mode: AlphaMode::Blend,
+            unlit: true,
+            ..default()
+        }),
+    };
+    commands.insert_resource(assets);
+}
+
+fn spawn_landing_marker(
+    trigger: Trigger<OnAdd, Planet>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut commands: Commands,
+) {
+    commands.entity(trigger.target()).with_child((
+        Name::new("Landing Marker"),
+        LandingMarker,
+        Transform::default(),
+        Visibility::Hidden,
+        Mesh3d(meshes.add(Torus::new(DROP_SIZE, DROP_SIZE * 1.5))),
+        MeshMaterial3d(materials.add(Color::NONE)),
+    ));
+}
+
+fn check(query: Single<(&mut DropTargeting, &ActionState<OrbitalDropActions>)>) {
+    let (mut targeting, action_state) = query.into_inner();
+    if action_state.just_pressed(&OrbitalDropActions::Arm) {
+        targeting.armed = !targeting.armed;
+    }
+    if action_state.just_pressed(&OrbitalDropActions::CyclePayload) {
+        targeting.payload = targeting.payload.next();
+        info!("Selected payload: {:?}", targeting.payload);
+    }
+    if action_state.just_pressed(&OrbitalDropActions::Cancel) {
+        targeting.armed = false;
+    }
+}
+
+fn update_target(
+    window: Single<&Window, With<PrimaryWindow>>,
+    camera: Single<(&Camera, &GlobalTransform), With<Camera3d>>,
+    mut terrain: TerrainRayCast,
+    surface: PlanetSurface,
+    assets: Res<DropAssets>,
+    targeting: Single<&mut DropTargeting>,
+    marker: Single<
+        (
+            &mut Transform,
+            &mut Visibility,
+            &mut MeshMaterial3d<StandardMaterial>,
+        ),
+        With<LandingMarker>,
+    >,
+) {
+    let mut targeting = targeting.into_inner();
+    let (mut marker_transform, mut marker_visibility, mut marker_material) = marker.into_inner();
+    targeting.target = None;
+    *marker_visibility = Visibility::Hidden;
+    if !targeting.armed {
+        return;
+    }
+
+    let (camera, camera_transform) = camera.into_inner();
+    let Some(ray) = window
+        .cursor_position()
+        .and_then(|cursor| camera.viewport_to_world(camera_transform, cursor).ok())
+    else {
+        return;
+    };
+    let Some((_, local_point)) = terrain.cast_ray(ray, |_| false) else {
+        return;
+    };
+
+    let site = surface.sample(local_point);
+    *marker_transform = Transform::from_translation(site.position)
+        .with_rotation(Quat::from_rotation_arc(Vec3::Y, site.normal));
+    *marker_visibility = Visibility::Inherited;
+    marker_material.0 = if site.is_land {
+        assets.land_material.clone()
+    } else {
+        assets.water_material.clone()
+    };
+    targeting.target = Some(site);
+}
+
+fn deploy(
+    query: Single<(&mut DropTargeting, &ActionState<OrbitalDropActions>)>,
+    planet: Single<Entity, With<Planet>>,
+    surface: PlanetSurface,
+    assets: Res<DropAssets>,
+    mut commands: Commands,
+) {
+    let (mut targeting, action_state) = query.into_inner();
+    if !action_state.just_pressed(&OrbitalDropActions::Confirm) {
+        return;
+    }
+    let Some(site) = targeting.target.take() else {
+        return;
+    };
+    targeting.armed = false;
+
+    let start = site.direction * surface.radius() * ORBIT_ALTITUDE;
+    commands.entity(planet.into_inner()).with_child((
+        Name::new("Orbital Drop"),
+        OrbitalDrop {
+            site,
+            payload: targeting.payload,
+        },
+        Descending { elapsed: 0.0 },
+        Transform::from_translation(start)
+            .with_rotation(Quat::from_rotation_arc(Vec3::Y, site.direction)),
+        Visibility::Inherited,
+        Mesh3d(assets.drop_mesh.clone()),
+        MeshMaterial3d(assets.drop_material.clone()),
+    ));
+}
+
+fn descend(
+    time: Res<Time>,
+    surface: PlanetSurface,
+    mut query: Query<(Entity, &OrbitalDrop, &mut Descending, &mut Transform)>,
+    mut commands: Commands,
+) {
+    for (entity, drop, mut descending, mut transform) in &mut query {
+        descending.elapsed += time.delta_secs();
+        let progress = (descending.elapsed / DESCENT_DURATION_SECS).min(1.0);
+        let start = drop.site.direction * surface.radius() * ORBIT_ALTITUDE;
+        // Drops that miss the land splash down on the ocean surface instead.
+        let touchdown = if drop.site.is_land {
+            drop.site.position
+        } else {
+            drop.site.direction * surface.sea_level_radius()
+        };
+        // Accelerate towards the ground like something falling out of orbit.
+        transform.translation = start.lerp(touchdown, progress * progress);
+        if progress < 1.0 {
+            continue;
+        }
+
+        let mut entity_commands = commands.entity(entity);
+        entity_commands.remove::<Descending>();
+        if drop.site.is_land {
+            transform.rotation = Quat::from_rotation_arc(Vec3::Y, drop.site.normal);
+            entity_commands.insert(Landed);
+            commands.trigger(DropLanded {
+                entity,
+                site: drop.site,
+                payload: drop.payload,
+            });
+        } else {
+            entity_commands.insert(Sinking {
+                elapsed: 0.0,
+                from: touchdown,
+            });
+        }
+    }
+}
+
+fn sink(
+    time: Res<Time>,
+    mut query: Query<(Entity, &OrbitalDrop, &mut Sinking, &mut Transform)>,
+    mut commands: Commands,
+) {
+    for (entity, drop, mut sinking, mut transform) in &mut query {
+        sinking.elapsed += time.delta_secs();
+        let progress = (sinking.elapsed / SINK_DURATION_SECS).min(1.0);
+        let seabed = drop.site.position - drop.site.direction * SINK_DEPTH;
+        transform.translation = sinking.from.lerp(seabed, progress);
+        transform.scale = Vec3::splat(1.0 - progress);
+        if progress >= 1.0 {
+            commands.entity(entity).despawn();
+            commands.trigger(DropLost { site: drop.site });
+        }
+    }
+}
@@ -0,0 +1,279 @@
+//! Biomes classify the surface by its climate, following a Whittaker diagram of temperature
+//! against precipitation. The planet's terrain is tinted with the colour of its biomes.
+
+use bevy::{prelude::*, render::mesh::VertexAttributeValues};
+use common::{schedules::Simulation, states::Screen, system_sets::SimulationSet};
+use simulation::SimulationClock;
+
+use crate::{
+    Planet,
+    mesh_generation::TerrainMeshReady,
+    moisture::initialize_moisture,
+    overlay::{Overlay, OverlayGradient, RegisterOverlay},
+    planet_grid::PlanetGrid,
+    planet_settings::PlanetSettings,
+    terrain_face::{ATTRIBUTE_SEA_LEVEL_ELEVATION, TerrainFace},
+};
+
+/// Land lower than this above sea level, in world units, is beach.
+const BEACH_HEIGHT: f32 = 0.01;
+/// Land higher than this above sea level, in world units, is too steep and windswept to
+/// hold any soil.
+const ROCK_HEIGHT: f32 = 0.25;
+/// Simulation ticks between two repaints of the terrain. Biomes only shift with the
+/// seasons, so there is no need to check every vertex on every tick.
+const REPAINT_INTERVAL: u64 = 30;
+/// Upper temperature bounds of the rows in [`WHITTAKER`], in degrees Celsius.
+const TEMPERATURE_BANDS: [f32; 3] = [-10.0, 0.0, 20.0];
+/// Upper precipitation bounds of the columns in [`WHITTAKER`], in rain per day.
+const PRECIPITATION_BANDS: [f32; 2] = [0.1, 0.4];
+
+/// Barren rock shows the planet's colour on the terrain, but the biome overlay has to tell
+/// it apart from the other biomes on every planet.
+const BARREN_ROCK_OVERLAY_COLOR: Color = Color::srgb(0.45, 0.42, 0.4);
+
+/// Biomes by temperature (rows, cold to hot) and precipitation (columns, dry to wet).
+const WHITTAKER: [[Biome; 3]; 4] = {
+    use Biome::*;
+    [
+        [Ice, Ice, Ice],
+        [Tundra, Tundra, Forest],
+        [Desert, Grassland, Forest],
+        [Desert, Grassland, Forest],
+    ]
+};
+
+pub(crate) fn plugin(app: &mut App) {
+    app.register_type::<Biome>()
+        .register_overlay(
+            Overlay {
+                name: "Biomes",
+                key: Some(KeyCode::KeyB),
+                unit: "",
+                gradient: OverlayGradient::Categories(
+                    Biome::ALL
+                        .map(|biome| (biome.name(), biome.overlay_color()))
+                        .to_vec(),
+                ),
+            },
+            sample_biome,
+        )
+        .register_overlay(
+            Overlay {
+                name: "Biomass",
+                key: Some(KeyCode::KeyV),
+                unit: "",
+                gradient: OverlayGradient::Stops(vec![
+                    (0.0, Color::srgb(0.45, 0.35, 0.2)),
+                    (1.0, Color::srgb(0.1, 0.6, 0.1)),
+                ]),
+            },
+            sample_biomass,
+        )
+        .add_observer(tint_generated_face)
+        .add_systems(
+            Update,
+            (
+                initialize_biomes
+                    .after(initialize_moisture)
+                    .run_if(resource_added::<PlanetGrid>),
+                tint_planet.run_if(resource_exists_and_changed::<PlanetGrid>),
+            )
+                .chain()
+                .run_if(in_state(Screen::Gameplay)),
+        )
+        .add_systems(Simulation, update_biomes.in_set(SimulationSet::Ecology));
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Reflect)]
+pub enum Biome {
+    #[default]
+    Ocean,
+    Beach,
+    Desert,
+    Grassland,
+    Forest,
+    Tundra,
+    Ice,
+    BarrenRock,
+}
+
+impl Biome {
+    /// Every biome, in the order of their declaration.
+    pub const ALL: [Biome; 8] = [
+        Biome::Ocean,
+        Biome::Beach,
+        Biome::Desert,
+        Biome::Grassland,
+        Biome::Forest,
+        Biome::Tundra,
+        Biome::Ice,
+        Biome::BarrenRock,
+    ];
+
+    /// Looks up the biome for a climate. `elevation` is in world units above sea level.
+    pub fn classify(temperature: f32, precipitation: f32, elevation: f32) -> Self {
+        let band = |bounds: &[f32], value: f32| {
+            bounds
+                .iter()
+                .position(|&bound| value < bound)
+                .unwrap_or(bounds.len())
+        };
+        let climate = WHITTAKER[band(&TEMPERATURE_BANDS, temperature)]
+            [band(&PRECIPITATION_BANDS, precipitation)];
+        match climate {
+            // Ice covers frozen oceans and mountains alike.
+            Biome::Ice => Biome::Ice,
+            _ if elevation < 0.0 => Biome::Ocean,
+            _ if elevation < BEACH_HEIGHT => Biome::Beach,
+            _ if elevation > ROCK_HEIGHT => Biome::BarrenRock,
+            climate => climate,
+        }
+    }
+
+    /// The colour the terrain is tinted with. Barren rock shows the planet's base colour.
+    pub fn color(self, settings: &PlanetSettings) -> Color {
+        match self {
+            Biome::Ocean => Color::srgb(0.15, 0.25, 0.35),
+            Biome::Beach => Color::srgb(0.93, 0.85, 0.6),
+            Biome::Desert => Color::srgb(0.85, 0.7, 0.4),
+            Biome::Grassland => Color::srgb(0.5, 0.7, 0.25),
+            Biome::Forest => Color::srgb(0.1, 0.4, 0.15),
+            Biome::Tundra => Color::srgb(0.55, 0.55, 0.45),
+            Biome::Ice => Color::srgb(0.95, 0.97, 1.0),
+            Biome::BarrenRock => settings.color,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Biome::Ocean => "Ocean",
+            Biome::Beach => "Beach",
+            Biome::Desert => "Desert",
+            Biome::Grassland => "Grassland",
+            Biome::Forest => "Forest",
+            Biome::Tundra => "Tundra",
+            Biome::Ice => "Ice",
+            Biome::BarrenRock => "Barren Rock",
+        }
+    }
+
+    fn overlay_color(self) -> Color {
+        let settings = PlanetSettings {
+            color: BARREN_ROCK_OVERLAY_COLOR,
+            ..default()
+        };
+        self.color(&settings)
+    }
+}
+
+fn classify_cells(grid: &mut PlanetGrid) {
+    for cell in 0..grid.len() {
+        grid.biome[cell] = Biome::classify(
+            grid.temperature[cell],
+            grid.precipitation[cell],
+            grid.elevation[cell],
+        );
+    }
+}
+
+fn initialize_biomes(mut grid: ResMut<PlanetGrid>) {
+    classify_cells(&mut grid);
+}
+
+fn update_biomes(mut grid: ResMut<PlanetGrid>) {
+    classify_cells(&mut grid);
+}
+
+/// Paints the terrain with the biome colours whenever a cell changed its biome, at most once
+/// every [`REPAINT_INTERVAL`] ticks.
+fn tint_planet(
+    grid: Res<PlanetGrid>,
+    clock: Res<SimulationClock>,
+    settings: Res<PlanetSettings>,
+    planet: Single<&Children, With<Planet>>,
+    faces: Query<&Mesh3d, With<TerrainFace>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    // The tick of the last repaint and the biomes that were painted then.
+    mut painted: Local<(Option<u64>, Vec<Biome>)>,
+) {
+    let (painted_at, painted_biomes) = &mut *painted;
+    if grid.is_added() {
+        *painted_at = None;
+        painted_biomes.clear();
+    }
+    if painted_at.is_some_and(|tick| (tick..tick + REPAINT_INTERVAL).contains(&clock.ticks))
+        || *painted_biomes == grid.biome
+    {
+        return;
+    }
+    *painted_at = Some(clock.ticks);
+    painted_biomes.clone_from(&grid.biome);
+
+    for mesh in faces.iter_many(planet.into_inner()) {
+        if let Some(mesh) = meshes.get_mut(mesh) {
+            paint_biomes(mesh, &grid, &settings);
+        }
+    }
+}
+
+fn tint_generated_face(
+    trigger: Trigger<TerrainMeshReady>,
+    grid: Res<PlanetGrid>,
+    settings: Res<PlanetSettings>,
+    faces: Query<(&Mesh3d, &ChildOf), With<TerrainFace>>,
+    planets: Query<(), With<Planet>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    let Ok((mesh, child_of)) = faces.get(trigger.target()) else {
+        return;
+    };
+    if !planets.contains(child_of.parent()) {
+        return;
+    }
+    if let Some(mesh) = meshes.get_mut(mesh) {
+        paint_biomes(mesh, &grid, &settings);
+    }
+}
+
+/// Every vertex is classified on its own, using its exact elevation and the climate of the
+/// cell it lies in, so coastlines stay sharper than the grid.
+fn paint_biomes(mesh: &mut Mesh, grid: &PlanetGrid, settings: &PlanetSettings) {
+    let (
+        Some(VertexAttributeValues::Float32x3(positions)),
+        Some(VertexAttributeValues::Float32(elevations)),
+    ) = (
+        mesh.attribute(Mesh::ATTRIBUTE_POSITION),
+        mesh.attribute(ATTRIBUTE_SEA_LEVEL_ELEVATION),
+    )
+    else {
+        return;
+    };
+    let colors: Vec<[f32; 4]> = positions
+        .iter()
+        .zip(elevations)
+        .map(|(&position, &elevation)| {
+            let cell = grid.cell_at(Vec3::from(position));
+            Biome::classify(grid.temperature[cell], grid.precipitation[cell], elevation)
+                .color(settings)
+                .to_linear()
+                .to_f32_array()
+        })
+        .collect();
+    mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
+}
+
+/// Returns the index of the biome in [`Biome::ALL`] in each direction.
+fn sample_biome(In(directions): In<Vec<Vec3>>, grid: Res<PlanetGrid>) -> Vec<f32> {
+    directions
+        .into_iter()
+        .map(|direction| grid.biome[grid.cell_at(direction)] as usize as f32)
+        .collect()
+}
+
+fn sample_biomass(In(directions): In<Vec<Vec3>>, grid: Res<PlanetGrid>) -> Vec<f32> {
+    directions
+        .into_iter()
+        .map(|direction| grid.sample(&grid.biomass, direction))
+        .collect()
+}
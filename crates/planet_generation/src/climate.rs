@@ -0,0 +1,131 @@
+//! A simple climate model that fills the temperature layer of the [`PlanetGrid`].
+//!
+//! Temperatures are driven by the insolation at a cell's latitude, cool down with altitude
+//! and are shifted globally by the greenhouse effect. Seasons move the warmest latitude
+//! back and forth between the tropics over the course of a year.
+
+use bevy::prelude::*;
+use common::{schedules::Simulation, states::Screen, system_sets::SimulationSet};
+use serde::Deserialize;
+use simulation::SimulationClock;
+
+use crate::{
+    overlay::{Overlay, OverlayGradient, RegisterOverlay},
+    planet_grid::PlanetGrid,
+};
+
+/// Colours of the temperature overlay, in degrees Celsius.
+const TEMPERATURE_GRADIENT: [(f32, Color); 5] = [
+    (-30.0, Color::linear_rgb(0.02, 0.05, 0.5)),
+    (-10.0, Color::linear_rgb(0.2, 0.6, 1.0)),
+    (0.0, Color::linear_rgb(0.9, 0.95, 1.0)),
+    (15.0, Color::linear_rgb(1.0, 0.8, 0.1)),
+    (30.0, Color::linear_rgb(0.8, 0.05, 0.0)),
+];
+
+pub(crate) fn plugin(app: &mut App) {
+    app.register_type::<ClimateSettings>()
+        .init_resource::<ClimateSettings>()
+        .register_overlay(
+            Overlay {
+                name: "Temperature",
+                key: Some(KeyCode::KeyT),
+                unit: "°C",
+                gradient: OverlayGradient::Stops(TEMPERATURE_GRADIENT.to_vec()),
+            },
+            sample_temperature,
+        )
+        .add_systems(
+            Update,
+            initialize_temperature
+                .run_if(resource_added::<PlanetGrid>)
+                .run_if(in_state(Screen::Gameplay)),
+        )
+        .add_systems(
+            Simulation,
+            update_temperature.in_set(SimulationSet::Environment),
+        );
+}
+
+/// Global parameters of the climate model. Temperatures are in degrees Celsius.
+#[derive(Clone, Deserialize, Reflect, Resource)]
+#[reflect(Resource)]
+#[serde(default)]
+pub struct ClimateSettings {
+    /// Sea level temperature where the sun stands at its zenith.
+    pub zenith_temperature: f32,
+    /// Sea level temperature where the sun doesn't shine at all.
+    pub night_temperature: f32,
+    /// Temperature drop per world unit of altitude above sea level.
+    pub lapse_rate: f32,
+    /// Warming applied to the whole planet by its atmosphere.
+    pub greenhouse: f32,
+    /// Tilt of the planet's axis in degrees, which causes seasons.
+    pub axial_tilt: f32,
+    /// Fraction of the difference to the equilibrium temperature that is closed every day.
+    pub response: f32,
+}
+
+impl Default for ClimateSettings {
+    fn default() -> Self {
+        Self {
+            zenith_temperature: 30.0,
+            night_temperature: -40.0,
+            lapse_rate: 60.0,
+            greenhouse: 0.0,
+            axial_tilt: 23.5,
+            response: 0.05,
+        }
+    }
+}
+
+impl ClimateSettings {
+    /// The temperature a cell would settle at if the season stayed the same.
+    fn equilibrium_temperature(&self, direction: Vec3, elevation: f32, year_fraction: f32) -> f32 {
+        let latitude = direction.y.clamp(-1.0, 1.0).asin();
+        let subsolar_latitude =
+            self.axial_tilt.to_radians() * (year_fraction * std::f32::consts::TAU).sin();
+        let insolation = (latitude - subsolar_latitude).cos().max(0.0);
+        self.night_temperature
+            + (self.zenith_temperature - self.night_temperature) * insolation
+            + self.greenhouse
+            - self.lapse_rate * elevation.max(0.0)
+    }
+}
+
+pub(crate) fn initialize_temperature(
+    settings: Res<ClimateSettings>,
+    clock: Res<SimulationClock>,
+    mut grid: ResMut<PlanetGrid>,
+) {
+    for cell in 0..grid.len() {
+        grid.temperature[cell] = settings.equilibrium_temperature(
+            grid.direction(cell),
+            grid.elevation[cell],
+            clock.year_fraction(),
+        );
+    }
+}
+
+pub(crate) fn update_temperature(
+    settings: Res<ClimateSettings>,
+    clock: Res<SimulationClock>,
+    mut grid: ResMut<PlanetGrid>,
+) {
+    for cell in 0..grid.len() {
+        let equilibrium = settings.equilibrium_temperature(
+            grid.direction(cell),
+            grid.elevation[cell],
+            clock.year_fraction(),
+        );
+        let temperature = &mut grid.temperature[cell];
+        *temperature += (equilibrium - *temperature) * settings.response;
+    }
+}
+
+fn sample_temperature(In(directions): In<Vec<Vec3>>, grid: Res<PlanetGrid>) -> Vec<f32> {
+    directions
+        .into_iter()
+        .map(|direction| grid.sample(&grid.temperature, direction))
+        .collect()
+}
@@ -1,62 +1,76 @@
 use bevy::prelude::*;
 use leafwing_input_manager::prelude::*;
 
-use crate::{GeothermalOverlay, Planet};
+use crate::overlay::{OPACITY_STEP, OverlaySettings, Overlays};
 
 #[derive(Actionlike, Clone, Debug, Eq, Hash, PartialEq, Reflect)]
 pub(crate) enum PlanetActions {
-    ToggleGeothermalOverlay,
+    /// Cycles forward through the [`Overlays`].
+    Next,
+    /// Cycles backward through the [`Overlays`].
+    Previous,
+    /// Shows the overlay with this index in the [`Overlays`], or hides it if it is shown.
+    Toggle(usize),
+    /// Makes the shown overlay less transparent.
+    IncreaseOpacity,
+    /// Makes the shown overlay more transparent.
+    DecreaseOpacity,
 }
 
+/// Which overlay, if any, is shown on top of the planet.
 #[derive(Component, Default, Reflect)]
 #[reflect(Component)]
 pub struct PlanetState {
-    pub geothermal_overlay: bool,
+    /// The [`Overlay::name`](crate::Overlay::name) of the shown overlay.
+    pub overlay: Option<&'static str>,
 }
 
-#[derive(Event)]
-struct StateChange(bool);
-
-pub(crate) fn setup(mut commands: Commands) {
-    let input_map = InputMap::new([(PlanetActions::ToggleGeothermalOverlay, KeyCode::KeyG)]);
-    commands
-        .spawn((
-            Name::new("PlanetControls"),
-            InputManagerBundle::with_map(input_map),
-            PlanetState::default(),
-        ))
-        .observe(on_state_change);
-}
-
-pub(crate) fn check(
-    query: Single<(Entity, &mut PlanetState, &ActionState<PlanetActions>)>,
-    mut commands: Commands,
-) {
-    let (entity, mut planet_state, action_state) = query.into_inner();
-    if action_state.just_pressed(&PlanetActions::ToggleGeothermalOverlay) {
-        planet_state.geothermal_overlay = !planet_state.geothermal_overlay;
-        commands
-            .entity(entity)
-            .trigger(StateChange(planet_state.geothermal_overlay));
+pub(crate) fn setup(overlays: Res<Overlays>, mut commands: Commands) {
+    let mut input_map = InputMap::new([
+        (PlanetActions::Next, KeyCode::BracketRight),
+        (PlanetActions::Previous, KeyCode::BracketLeft),
+        (PlanetActions::IncreaseOpacity, KeyCode::Equal),
+        (PlanetActions::DecreaseOpacity, KeyCode::Minus),
+    ]);
+    for (index, overlay) in overlays.iter().enumerate() {
+        if let Some(key) = overlay.key {
+            input_map.insert(PlanetActions::Toggle(index), key);
+        }
     }
+    commands.spawn((
+        Name::new("PlanetControls"),
+        InputManagerBundle::with_map(input_map),
+        PlanetState::default(),
+    ));
 }
 
-fn on_state_change(
-    trigger: Trigger<StateChange>,
-    planet: Single<&mut Visibility, (With<Planet>, Without<GeothermalOverlay>)>,
-    geothermal: Single<&mut Visibility, (Without<Planet>, With<GeothermalOverlay>)>,
+pub(crate) fn check(
+    query: Single<(&mut PlanetState, &ActionState<PlanetActions>)>,
+    overlays: Res<Overlays>,
+    mut settings: ResMut<OverlaySettings>,
 ) {
-    let state_change = trigger.event();
-    let mut planet_visibility = planet.into_inner();
-    let mut geothermal_visibility = geothermal.into_inner();
-    match state_change.0 {
-        true => {
-            *planet_visibility = Visibility::Hidden;
-            *geothermal_visibility = Visibility::Inherited;
-        }
-        false => {
-            *planet_visibility = Visibility::Inherited;
-            *geothermal_visibility = Visibility::Hidden;
+    let (mut planet_state, action_state) = query.into_inner();
+    let shown = planet_state
+        .overlay
+        .and_then(|name| overlays.index_of(name));
+    let mut next = shown;
+    let mut opacity = settings.opacity;
+    for action in action_state.get_just_pressed() {
+        match action {
+            PlanetActions::Next => next = overlays.next(next),
+            PlanetActions::Previous => next = overlays.previous(next),
+            PlanetActions::Toggle(index) if next == Some(index) => next = None,
+            PlanetActions::Toggle(index) => next = Some(index),
+            PlanetActions::IncreaseOpacity => opacity += OPACITY_STEP,
+            PlanetActions::DecreaseOpacity => opacity -= OPACITY_STEP,
         }
     }
+    if next != shown {
+        planet_state.overlay = next
+            .and_then(|index| overlays.get(index))
+            .map(|overlay| overlay.name);
+    }
+    if opacity != settings.opacity {
+        settings.opacity = opacity.clamp(OPACITY_STEP, 1.0);
+    }
 }
@@ -0,0 +1,470 @@
+//! Erosion carves valleys and river channels into the terrain, which noise alone doesn't
+//! produce. It runs once over a heightfield covering the whole planet on the
+//! [`AsyncComputeTaskPool`], and the change it makes is added to the elevation of the noise
+//! layers wherever the terrain is sampled.
+//!
+//! Hydraulic erosion follows raindrops downhill. A drop picks up sediment where it speeds
+//! up and drops it where it slows down or is full. Thermal erosion then lets material slump
+//! down slopes that are too steep to hold it.
+
+use std::{
+    collections::HashMap,
+    f32::consts::FRAC_PI_2,
+    sync::{Arc, OnceLock},
+};
+
+use bevy::{
+    prelude::*,
+    tasks::{AsyncComputeTaskPool, Task, block_on, poll_once},
+};
+use serde::Deserialize;
+
+use crate::{
+    planet_preset::apply_planet_preset,
+    planet_seed::{SeededRandom, apply_planet_seed},
+    planet_settings::PlanetSettings,
+    terrain_face::{DIRECTIONS, TerrainFace, grid_coordinate},
+};
+
+/// The least sediment a drop can carry, so it keeps eroding on flat ground.
+const MIN_SEDIMENT_CAPACITY: f32 = 0.01;
+
+pub(crate) fn plugin(app: &mut App) {
+    app.add_systems(
+        Update,
+        (
+            discard_eroded_terrain
+                .after(apply_planet_preset)
+                .after(apply_planet_seed)
+                .run_if(resource_changed::<PlanetSettings>),
+            finish_erosion.run_if(resource_exists::<ErosionTask>),
+        ),
+    );
+}
+
+#[derive(Clone, Deserialize, Reflect)]
+#[serde(default)]
+pub(crate) struct ErosionSettings {
+    pub(crate) enabled: bool,
+    /// Number of samples along one edge of a face of the eroded heightfield.
+    pub(crate) resolution: u32,
+    /// Number of raindrops that run over the terrain.
+    pub(crate) droplets: u32,
+    /// Number of steps a raindrop takes before it has evaporated.
+    pub(crate) droplet_lifetime: u32,
+    /// How much a drop keeps its direction instead of following the slope, from `0.0` to `1.0`.
+    pub(crate) inertia: f32,
+    /// How much sediment a drop can carry for its speed, water and the slope it runs down.
+    pub(crate) sediment_capacity: f32,
+    /// The share of its free capacity a drop fills with eroded material per step.
+    pub(crate) erosion_rate: f32,
+    /// The share of its excess sediment a drop deposits per step.
+    pub(crate) deposition_rate: f32,
+    /// The share of its water a drop loses per step.
+    pub(crate) evaporation_rate: f32,
+    /// How quickly drops speed up downhill.
+    pub(crate) gravity: f32,
+    /// Number of passes of thermal erosion after the raindrops.
+    pub(crate) thermal_iterations: u32,
+    /// The steepest slope that holds material, as elevation per radian.
+    pub(crate) talus_slope: f32,
+    /// The share of the material above the talus slope that slumps per pass.
+    pub(crate) thermal_rate: f32,
+    /// Where the raindrops fall. Derived from the planet seed.
+    #[reflect(ignore)]
+    #[serde(skip)]
+    pub(crate) seed: u32,
+}
+
+impl Default for ErosionSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            resolution: 128,
+            droplets: 100_000,
+            droplet_lifetime: 30,
+            inertia: 0.05,
+            sediment_capacity: 4.0,
+            erosion_rate: 0.3,
+            deposition_rate: 0.3,
+            evaporation_rate: 0.01,
+            gravity: 4.0,
+            thermal_iterations: 20,
+            talus_slope: 0.1,
+            thermal_rate: 0.5,
+            seed: 0,
+        }
+    }
+}
+
+/// The eroded heightfield, filled in by an [`ErosionTask`] and shared by every clone of the
+/// [`PlanetSettings`], so all chunks of the terrain see the same erosion.
+#[derive(Clone, Default)]
+pub(crate) struct ErodedTerrain(Arc<OnceLock<ErosionMap>>);
+
+impl ErodedTerrain {
+    /// Returns how much erosion changed the elevation in a direction. Until the erosion has
+    /// finished, the terrain is sampled as if it had none.
+    pub(crate) fn change(&self, direction: Vec3) -> f32 {
+        self.0.get().map_or(0.0, |map| map.sample(direction))
+    }
+}
+
+/// The erosion of the terrain, running on the [`AsyncComputeTaskPool`]. The terrain meshes
+/// and the [`PlanetGrid`](crate::PlanetGrid) wait for it, so they are built eroded.
+///
+/// Replacing or removing this resource drops the task, and a task started with outdated
+/// settings never fills in the erosion of newer ones.
+#[derive(Resource)]
+pub(crate) struct ErosionTask {
+    eroded: ErodedTerrain,
+    task: Task<ErosionMap>,
+}
+
+impl ErosionTask {
+    /// Starts eroding the terrain, or returns `None` if the settings have no erosion or it
+    /// is done already.
+    pub(crate) fn start(settings: &PlanetSettings) -> Option<Self> {
+        if !settings.erosion.enabled || settings.eroded.0.get().is_some() {
+            return None;
+        }
+        let eroding = settings.clone();
+        let task = AsyncComputeTaskPool::get().spawn(async move { ErosionMap::erode(&eroding) });
+        Some(Self {
+            eroded: settings.eroded.clone(),
+            task,
+        })
+    }
+}
+
+/// A heightfield with the same layout as the terrain faces. Points on the edges of faces
+/// exist once and are shared by the adjacent faces.
+struct ErosionMap {
+    resolution: u32,
+    /// The point of every grid position, face by face and row by row.
+    points: Vec<usize>,
+    /// The four closest points around every point, on the same or an adjacent face.
+    neighbours: Vec<[usize; 4]>,
+    /// How much erosion changed the elevation at every point.
+    change: Vec<f32>,
+}
+
+impl ErosionMap {
+    fn erode(settings: &PlanetSettings) -> Self {
+        let erosion = &settings.erosion;
+        let mut map = Self::new(erosion.resolution.max(2));
+        // Heights are measured in units of the grid spacing, so the difference between two
+        // neighbours is the slope between them.
+        let spacing = FRAC_PI_2 / (map.resolution - 1) as f32;
+        let directions = map.directions();
+        let initial: Vec<f32> = directions
+            .iter()
+            .map(|&direction| settings.calculate_noise_elevation(direction) / spacing)
+            .collect();
+        let mut heights = initial.clone();
+
+        map.run_droplets(erosion, &mut heights);
+        map.slump(erosion, &mut heights);
+
+        map.change = heights
+            .iter()
+            .zip(&initial)
+            .map(|(height, initial)| (height - initial) * spacing)
+            .collect();
+        map
+    }
+
+    fn new(resolution: u32) -> Self {
+        let spacings = resolution - 1;
+        let mut points = Vec::with_capacity(6 * (resolution * resolution) as usize);
+        let mut point_of_direction = HashMap::new();
+        for local_up in DIRECTIONS {
+            let face = TerrainFace::new(local_up);
+            for y in 0..resolution {
+                for x in 0..resolution {
+                    let coordinate = grid_coordinate(UVec2::new(x, y), spacings);
+                    let direction = face.point_on_cube_face(coordinate).normalize();
+                    let next = point_of_direction.len();
+                    let point = *point_of_direction
+                        .entry(direction.to_array().map(f32::to_bits))
+                        .or_insert(next);
+                    points.push(point);
+                }
+            }
+        }
+
+        let mut map = Self {
+            resolution,
+            points,
+            neighbours: vec![[0; 4]; point_of_direction.len()],
+            change: vec![0.0; point_of_direction.len()],
+        };
+        let mut visited = vec![false; map.change.len()];
+        for (face_index, local_up) in DIRECTIONS.into_iter().enumerate() {
+            let face = TerrainFace::new(local_up);
+            for y in 0..resolution {
+                for x in 0..resolution {
+                    let point = map.point(face_index, x, y);
+                    if visited[point] {
+                        continue;
+                    }
+                    visited[point] = true;
+                    let position = UVec2::new(x, y).as_vec2();
+                    let neighbours = [Vec2::NEG_X, Vec2::X, Vec2::NEG_Y, Vec2::Y].map(|offset| {
+                        // Off the edge of the face, this finds the point on the adjacent face.
+                        let uv = (position + offset) / spacings as f32;
+                        map.nearest_point(face.point_on_unit_cube(uv).normalize())
+                    });
+                    map.neighbours[point] = neighbours;
+                }
+            }
+        }
+        map
+    }
+
+    fn point(&self, face_index: usize, x: u32, y: u32) -> usize {
+        let resolution = self.resolution as usize;
+        self.points[(face_index * resolution + y as usize) * resolution + x as usize]
+    }
+
+    fn directions(&self) -> Vec<Vec3> {
+        let spacings = self.resolution - 1;
+        let mut directions = vec![Vec3::ZERO; self.change.len()];
+        for (face_index, local_up) in DIRECTIONS.into_iter().enumerate() {
+            let face = TerrainFace::new(local_up);
+            for y in 0..self.resolution {
+                for x in 0..self.resolution {
+                    let coordinate = grid_coordinate(UVec2::new(x, y), spacings);
+                    directions[self.point(face_index, x, y)] =
+                        face.point_on_cube_face(coordinate).normalize();
+                }
+            }
+        }
+        directions
+    }
+
+    /// Returns the face a direction passes through and its position on the face's grid.
+    fn grid_position(&self, direction: Vec3) -> (usize, Vec2) {
+        let face = TerrainFace::containing(direction);
+        let face_index = DIRECTIONS
+            .iter()
+            .position(|&local_up| local_up == face.local_up())
+            .unwrap();
+        let position = face.uv_of(direction) * (self.resolution - 1) as f32;
+        (face_index, position)
+    }
+
+    fn nearest_point(&self, direction: Vec3) -> usize {
+        let (face_index, position) = self.grid_position(direction);
+        let last = (self.resolution - 1) as f32;
+        let position = position.round().clamp(Vec2::ZERO, Vec2::splat(last));
+        self.point(face_index, position.x as u32, position.y as u32)
+    }
+
+    /// Returns the grid cell a position lies in and the position within the cell.
+    fn cell(&self, position: Vec2) -> (UVec2, Vec2) {
+        let last_cell = (self.resolution - 2) as f32;
+        let cell = position.floor().clamp(Vec2::ZERO, Vec2::splat(last_cell));
+        (
+            cell.as_uvec2(),
+            (position - cell).clamp(Vec2::ZERO, Vec2::ONE),
+        )
+    }
+
+    /// Returns the four points around a grid position, with their bilinear weights.
+    fn corners(&self, face_index: usize, position: Vec2) -> [(usize, f32); 4] {
+        let (UVec2 { x, y }, t) = self.cell(position);
+        [
+            (self.point(face_index, x, y), (1.0 - t.x) * (1.0 - t.y)),
+            (self.point(face_index, x + 1, y), t.x * (1.0 - t.y)),
+            (self.point(face_index, x, y + 1), (1.0 - t.x) * t.y),
+            (self.point(face_index, x + 1, y + 1), t.x * t.y),
+        ]
+    }
+
+    /// Returns the height and its gradient along the grid at a grid position.
+    fn height_and_gradient(
+        &self,
+        heights: &[f32],
+        face_index: usize,
+        position: Vec2,
+    ) -> (f32, Vec2) {
+        let corners = self.corners(face_index, position);
+        let [a, b, c, d] = corners.map(|(point, _)| heights[point]);
+        let height = corners
+            .iter()
+            .map(|&(point, weight)| heights[point] * weight)
+            .sum();
+        let (_, t) = self.cell(position);
+        let gradient = Vec2::new(
+            (b - a) * (1.0 - t.y) + (d - c) * t.y,
+            (c - a) * (1.0 - t.x) + (d - b) * t.x,
+        );
+        (height, gradient)
+    }
+
+    /// Moves one grid cell from a position. A step off the edge of a face continues on the
+    /// adjacent face, where the direction is expressed in that face's grid.
+    fn step(&self, face_index: usize, position: Vec2, direction: Vec2) -> (usize, Vec2, Vec2) {
+        let last = (self.resolution - 1) as f32;
+        let next = position + direction;
+        if next.cmpge(Vec2::ZERO).all() && next.cmple(Vec2::splat(last)).all() {
+            return (face_index, next, direction);
+        }
+        let face = TerrainFace::new(DIRECTIONS[face_index]);
+        let from = face.point_on_unit_cube(position / last).normalize();
+        let to = face.point_on_unit_cube(next / last).normalize();
+        let (next_face_index, next) = self.grid_position(to);
+        let next_face = TerrainFace::new(DIRECTIONS[next_face_index]);
+        let direction = (next - next_face.uv_of(from) * last).normalize_or_zero();
+        (next_face_index, next, direction)
+    }
+
+    fn run_droplets(&self, settings: &ErosionSettings, heights: &mut [f32]) {
+        let mut random = SeededRandom::new(u64::from(settings.seed));
+        for _ in 0..settings.droplets {
+            let (mut face_index, mut position) = self.grid_position(random.next_direction());
+            let mut direction = Vec2::ZERO;
+            let (mut speed, mut water, mut sediment) = (1.0, 1.0, 0.0);
+            for _ in 0..settings.droplet_lifetime {
+                let corners = self.corners(face_index, position);
+                let (height, gradient) = self.height_and_gradient(heights, face_index, position);
+                direction = (direction * settings.inertia - gradient * (1.0 - settings.inertia))
+                    .normalize_or_zero();
+                if direction == Vec2::ZERO {
+                    break;
+                }
+                let (next_face_index, next, next_direction) =
+                    self.step(face_index, position, direction);
+                let (next_height, _) = self.height_and_gradient(heights, next_face_index, next);
+                let height_difference = next_height - height;
+
+                let capacity = (-height_difference * speed * water * settings.sediment_capacity)
+                    .max(MIN_SEDIMENT_CAPACITY);
+                if sediment > capacity || height_difference > 0.0 {
+                    // Uphill the drop fills the pit it came from, otherwise it drops what it
+                    // can't carry.
+                    let deposit = if height_difference > 0.0 {
+                        height_difference.min(sediment)
+                    } else {
+                        (sediment - capacity) * settings.deposition_rate
+                    };
+                    sediment -= deposit;
+                    for (point, weight) in corners {
+                        heights[point] += deposit * weight;
+                    }
+                } else {
+                    // Never dig deeper than the next position, or the drop would dig a pit.
+                    let eroded =
+                        ((capacity - sediment) * settings.erosion_rate).min(-height_difference);
+                    sediment += eroded;
+                    for (point, weight) in corners {
+                        heights[point] -= eroded * weight;
+                    }
+                }
+
+                speed = (speed * speed - height_difference * settings.gravity)
+                    .max(0.0)
+                    .sqrt();
+                water *= 1.0 - settings.evaporation_rate;
+                (face_index, position, direction) = (next_face_index, next, next_direction);
+            }
+        }
+    }
+
+    fn slump(&self, settings: &ErosionSettings, heights: &mut [f32]) {
+        let mut moved = vec![0.0; heights.len()];
+        for _ in 0..settings.thermal_iterations {
+            moved.fill(0.0);
+            for (point, neighbours) in self.neighbours.iter().enumerate() {
+                for &neighbour in neighbours {
+                    let excess = heights[point] - heights[neighbour] - settings.talus_slope;
+                    if neighbour == point || excess <= 0.0 {
+                        continue;
+                    }
+                    // Half the excess levels the pair out. A point can lose material to all
+                    // four neighbours, so each of them gets a quarter of that at most.
+                    let amount = excess * settings.thermal_rate / 8.0;
+                    moved[point] -= amount;
+                    moved[neighbour] += amount;
+                }
+            }
+            for (height, moved) in heights.iter_mut().zip(&moved) {
+                *height += moved;
+            }
+        }
+    }
+
+    /// Returns the change of elevation in a direction, interpolated between the points.
+    fn sample(&self, direction: Vec3) -> f32 {
+        let (face_index, position) = self.grid_position(direction);
+        self.corners(face_index, position)
+            .iter()
+            .map(|&(point, weight)| self.change[point] * weight)
+            .sum()
+    }
+}
+
+/// Erosion is computed from the noise layers, so it has to start over whenever the
+/// settings change.
+fn discard_eroded_terrain(mut settings: ResMut<PlanetSettings>) {
+    settings.bypass_change_detection().eroded = ErodedTerrain::default();
+}
+
+fn finish_erosion(mut erosion: ResMut<ErosionTask>, mut commands: Commands) {
+    let Some(map) = block_on(poll_once(&mut erosion.task)) else {
+        return;
+    };
+    // Fails only if the erosion was already set, which is just as good.
+    let _ = erosion.eroded.0.set(map);
+    commands.remove_resource::<ErosionTask>();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        noise_filter::{FractalSettings, NoiseFilter, NoiseSettings},
+        planet_seed::PlanetSeed,
+    };
+
+    fn settings(seed: PlanetSeed) -> PlanetSettings {
+        let mut settings = PlanetSettings {
+            erosion: ErosionSettings {
+                enabled: true,
+                resolution: 16,
+                droplets: 2_000,
+                thermal_iterations: 4,
+                ..default()
+            },
+            ..default()
+        }
+        .with_layer(NoiseFilter::new(NoiseSettings::Simple(FractalSettings {
+            number_of_layers: 4,
+            strength: 0.3,
+            base_roughness: 1.5,
+            roughness: 2.0,
+            persistence: 0.5,
+            center: Vec3::ZERO,
+            min_value: 0.8,
+        })));
+        settings.reseed(seed);
+        settings
+    }
+
+    fn erode(seed: PlanetSeed) -> Vec<u32> {
+        ErosionMap::erode(&settings(seed))
+            .change
+            .iter()
+            .map(|change| change.to_bits())
+            .collect()
+    }
+
+    #[test]
+    fn same_seed_erodes_the_same() {
+        let first = erode(PlanetSeed(21));
+        assert!(first.iter().any(|&change| f32::from_bits(change) != 0.0));
+        assert_eq!(first, erode(PlanetSeed(21)));
+        assert_ne!(first, erode(PlanetSeed(22)));
+    }
+}
@@ -0,0 +1,54 @@
+use bevy::prelude::*;
+
+use crate::noise_filter::{FractalSettings, NoiseFilter, NoiseSettings};
+
+/// The name of the overlay that shows the [`GeothermalField`].
+pub const GEOTHERMAL_OVERLAY: &str = "Geothermal";
+
+/// The heat beneath the planet surface. The geothermal overlay visualizes this field.
+#[derive(Reflect, Resource)]
+#[reflect(Resource)]
+pub struct GeothermalField {
+    pub(crate) filter: NoiseFilter,
+    /// Heat from the plate boundaries and hot spots, if the terrain has tectonic plates.
+    pub(crate) tectonic_heat: Option<NoiseFilter>,
+}
+
+impl GeothermalField {
+    /// Returns the heat in the given direction from the planet center. The noise alone
+    /// ranges from `0.0` (no activity) to roughly `1.0`. On a planet with tectonic plates,
+    /// boundaries and hot spots add their heat on top, so the most active places can get
+    /// considerably hotter than that.
+    pub fn heat(&self, direction: Vec3) -> f32 {
+        let direction = direction.normalize();
+        let tectonic_heat = self
+            .tectonic_heat
+            .as_ref()
+            .map_or(0.0, |filter| filter.evaluate(direction));
+        self.filter.evaluate(direction) + tectonic_heat
+    }
+}
+
+impl Default for GeothermalField {
+    fn default() -> Self {
+        Self {
+            filter: NoiseFilter::new(NoiseSettings::Simple(FractalSettings {
+                number_of_layers: 1,
+                strength: 1.0,
+                base_roughness: 2.0,
+                roughness: 1.0,
+                persistence: 0.0,
+                center: Vec3::ZERO,
+                min_value: 0.0,
+            })),
+            tectonic_heat: None,
+        }
+    }
+}
+
+pub(crate) fn sample_heat(In(directions): In<Vec<Vec3>>, field: Res<GeothermalField>) -> Vec<f32> {
+    directions
+        .into_iter()
+        .map(|direction| field.heat(direction))
+        .collect()
+}
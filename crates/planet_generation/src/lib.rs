@@ -1,9 +1,37 @@
+mod biome;
+mod climate;
 mod controls;
-mod geothermal_material;
+mod erosion;
+mod geothermal_field;
+mod mesh_generation;
+mod moisture;
 mod noise_filter;
+mod overlay;
+mod overlay_material;
 mod planet_generation;
+mod planet_grid;
+mod planet_preset;
+mod planet_seed;
 mod planet_settings;
+mod planet_surface;
+mod tectonics;
+mod terrain_chunk;
 mod terrain_face;
 
+pub use biome::Biome;
+pub use climate::ClimateSettings;
 pub use controls::PlanetState;
+pub use geothermal_field::{GEOTHERMAL_OVERLAY, GeothermalField};
+pub use moisture::{MoistureSettings, prevailing_wind};
+pub use overlay::{Overlay, OverlayGradient, OverlaySettings, Overlays, RegisterOverlay};
 pub use planet_generation::*;
+pub use planet_grid::PlanetGrid;
+pub use planet_preset::{PlanetCatalog, PlanetPreset, PlanetPresets};
+pub use planet_seed::{PlanetSeed, SeedStream};
+pub use planet_settings::PlanetSettings;
+pub use planet_surface::{
+    FaceCoordinate, PlanetSurface, SurfaceSample, TerrainRayCast, direction_from_lat_lon,
+    lat_lon_from_direction,
+};
+pub use terrain_chunk::TerrainChunk;
+pub use terrain_face::{ATTRIBUTE_SEA_LEVEL_ELEVATION, TerrainFace};
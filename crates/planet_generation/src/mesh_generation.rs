@@ -0,0 +1,84 @@
+//! Builds terrain meshes on the [`AsyncComputeTaskPool`], so that generating a detailed
+//! planet doesn't stall the frame. Every face gets its own task and they run in parallel.
+
+use bevy::{
+    prelude::*,
+    tasks::{AsyncComputeTaskPool, Task, block_on, poll_once},
+};
+use common::{states::Screen, system_sets::AppSet};
+
+use crate::{
+    planet_generation::PendingTerrain, planet_settings::PlanetSettings,
+    terrain_chunk::TerrainChunk, terrain_face::TerrainFace,
+};
+
+pub(crate) fn plugin(app: &mut App) {
+    app.add_systems(
+        Update,
+        (finish_mesh_generation, remove_placeholder)
+            .chain()
+            .in_set(AppSet::Update)
+            .run_if(in_state(Screen::Gameplay)),
+    );
+}
+
+/// A mesh that is being generated for the [`TerrainFace`] on the same entity.
+///
+/// Replacing or removing this component drops the task, which cancels it. That way a
+/// generation started with outdated settings never overwrites a newer mesh.
+#[derive(Component)]
+pub(crate) struct MeshGeneration(Task<Mesh>);
+
+impl MeshGeneration {
+    pub(crate) fn start(face: TerrainFace, chunk: TerrainChunk, settings: PlanetSettings) -> Self {
+        let task = AsyncComputeTaskPool::get().spawn(async move { face.to_mesh(&settings, chunk) });
+        Self(task)
+    }
+}
+
+/// Triggered on a [`TerrainFace`] once its generated mesh is in place.
+#[derive(Event)]
+pub(crate) struct TerrainMeshReady;
+
+/// Stands in for the terrain until the first meshes have been generated.
+#[derive(Component)]
+pub(crate) struct PlanetPlaceholder;
+
+/// Puts finished meshes in place. Faces that already have a mesh reuse its asset.
+///
+/// Systems that start a new [`MeshGeneration`] must run after this one. Otherwise a task
+/// finishing in the same frame removes the generation that was just started.
+pub(crate) fn finish_mesh_generation(
+    mut faces: Query<(Entity, &mut MeshGeneration, Option<&Mesh3d>)>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut commands: Commands,
+) {
+    for (entity, mut generation, mesh_handle) in &mut faces {
+        let Some(mesh) = block_on(poll_once(&mut generation.0)) else {
+            continue;
+        };
+        let mut entity_commands = commands.entity(entity);
+        entity_commands.remove::<MeshGeneration>();
+        match mesh_handle.and_then(|handle| meshes.get_mut(handle)) {
+            Some(existing) => *existing = mesh,
+            None => {
+                entity_commands.insert(Mesh3d(meshes.add(mesh)));
+            }
+        }
+        entity_commands.trigger(TerrainMeshReady);
+    }
+}
+
+fn remove_placeholder(
+    pending: Option<Res<PendingTerrain>>,
+    generating: Query<(), With<MeshGeneration>>,
+    placeholders: Query<Entity, With<PlanetPlaceholder>>,
+    mut commands: Commands,
+) {
+    if pending.is_some() || !generating.is_empty() {
+        return;
+    }
+    for entity in &placeholders {
+        commands.entity(entity).despawn();
+    }
+}
@@ -0,0 +1,267 @@
+//! The water cycle: moisture evaporates from the ocean, is carried along by the prevailing
+//! winds and rains down when the air cools, most of all where it is forced up a mountain.
+//!
+//! The air on the far side of a mountain range has already lost most of its water, which
+//! leaves dry rain shadows behind high terrain.
+
+use bevy::prelude::*;
+use common::{schedules::Simulation, states::Screen, system_sets::SimulationSet};
+
+use crate::{
+    climate::{initialize_temperature, update_temperature},
+    overlay::{Overlay, OverlayGradient, RegisterOverlay},
+    planet_grid::PlanetGrid,
+};
+
+/// Days simulated up front, so the planet doesn't start out bone dry.
+const WARMUP_DAYS: u32 = 100;
+
+pub(crate) fn plugin(app: &mut App) {
+    app.register_type::<MoistureSettings>()
+        .init_resource::<MoistureSettings>()
+        .register_overlay(
+            Overlay {
+                name: "Moisture",
+                key: Some(KeyCode::KeyM),
+                unit: "",
+                gradient: OverlayGradient::Stops(vec![
+                    (0.0, Color::srgb(0.8, 0.7, 0.5)),
+                    (1.0, Color::srgb(0.4, 0.7, 0.9)),
+                    (4.0, Color::srgb(0.05, 0.15, 0.6)),
+                ]),
+            },
+            sample_moisture,
+        )
+        .add_systems(
+            Update,
+            initialize_moisture
+                .after(initialize_temperature)
+                .run_if(resource_added::<PlanetGrid>)
+                .run_if(in_state(Screen::Gameplay)),
+        )
+        .add_systems(
+            Simulation,
+            update_moisture
+                .after(update_temperature)
+                .in_set(SimulationSet::Environment),
+        );
+}
+
+/// Parameters of the water cycle. Amounts of water are in arbitrary units per cell.
+#[derive(Reflect, Resource)]
+#[reflect(Resource)]
+pub struct MoistureSettings {
+    /// Water evaporating from an ocean cell per day at 30 degrees Celsius.
+    pub evaporation: f32,
+    /// Water the air above a cell can hold at 0 degrees Celsius. Warmer air holds more.
+    pub saturation: f32,
+    /// Fraction of the moisture that rains down every day even if the air isn't saturated.
+    pub drizzle: f32,
+    /// Fraction of the moisture that moves on to the downwind cell every day.
+    pub transport: f32,
+    /// Fraction of the transported moisture that rains down per world unit the air rises.
+    pub orographic_lift: f32,
+    /// How quickly the precipitation layer follows the daily rainfall, between `0.0` and `1.0`.
+    pub precipitation_response: f32,
+}
+
+impl Default for MoistureSettings {
+    fn default() -> Self {
+        Self {
+            evaporation: 1.0,
+            saturation: 2.0,
+            drizzle: 0.05,
+            transport: 0.6,
+            orographic_lift: 15.0,
+            precipitation_response: 0.1,
+        }
+    }
+}
+
+impl MoistureSettings {
+    /// How much moisture the air can hold at the given temperature before it rains.
+    fn capacity(&self, temperature: f32) -> f32 {
+        // Roughly doubles every 10 degrees, like the real saturation vapour pressure.
+        self.saturation * (0.07 * temperature).exp()
+    }
+
+    /// Moves the water cycle forward by one day.
+    fn step(&self, grid: &mut PlanetGrid) {
+        let mut rainfall = vec![0.0; grid.len()];
+        for (cell, rained) in rainfall.iter_mut().enumerate() {
+            let temperature = grid.temperature[cell];
+            let mut moisture = grid.moisture[cell];
+            if grid.elevation[cell] < 0.0 {
+                moisture += self.evaporation * (temperature / 30.0).clamp(0.0, 1.5);
+            }
+            let rain = (moisture - self.capacity(temperature)).max(0.0) + moisture * self.drizzle;
+            *rained = rain;
+            grid.moisture[cell] = moisture - rain;
+        }
+
+        let mut moisture = grid.moisture.clone();
+        for cell in 0..grid.len() {
+            let downwind = downwind_cell(grid, cell);
+            let moved = grid.moisture[cell] * self.transport;
+            // The ocean surface is flat, so only land forces the air to rise.
+            let lift = grid.elevation[downwind].max(0.0) - grid.elevation[cell].max(0.0);
+            let rain = (moved * lift.max(0.0) * self.orographic_lift).min(moved);
+            moisture[cell] -= moved;
+            moisture[downwind] += moved - rain;
+            rainfall[downwind] += rain;
+        }
+        grid.moisture = moisture;
+
+        for (precipitation, rain) in grid.precipitation.iter_mut().zip(rainfall) {
+            *precipitation += (rain - *precipitation) * self.precipitation_response;
+        }
+    }
+}
+
+/// Direction the wind blows towards at the given point on the unit sphere.
+///
+/// Follows the three circulation cells of the Earth: easterly trade winds near the
+/// equator, westerlies in the mid latitudes and polar easterlies around the poles.
+pub fn prevailing_wind(direction: Vec3) -> Vec3 {
+    let east = direction.cross(Vec3::Y).try_normalize().unwrap_or(Vec3::X);
+    let north = east.cross(direction);
+    let latitude = direction.y.clamp(-1.0, 1.0).asin().to_degrees();
+    let poleward = north * latitude.signum();
+    match latitude.abs() {
+        l if l < 30.0 => -east - poleward * 0.3,
+        l if l < 60.0 => east + poleward * 0.3,
+        _ => -east - poleward * 0.3,
+    }
+    .normalize()
+}
+
+/// The neighbour that lies most closely in the direction of the prevailing wind.
+fn downwind_cell(grid: &PlanetGrid, cell: usize) -> usize {
+    let direction = grid.direction(cell);
+    let wind = prevailing_wind(direction);
+    grid.neighbours(cell)
+        .into_iter()
+        .max_by(|&a, &b| {
+            let alignment = |neighbour| (grid.direction(neighbour) - direction).dot(wind);
+            alignment(a).total_cmp(&alignment(b))
+        })
+        .unwrap()
+}
+
+pub(crate) fn initialize_moisture(settings: Res<MoistureSettings>, mut grid: ResMut<PlanetGrid>) {
+    for _ in 0..WARMUP_DAYS {
+        settings.step(&mut grid);
+    }
+}
+
+fn update_moisture(settings: Res<MoistureSettings>, mut grid: ResMut<PlanetGrid>) {
+    settings.step(&mut grid);
+}
+
+fn sample_moisture(In(directions): In<Vec<Vec3>>, grid: Res<PlanetGrid>) -> Vec<f32> {
+    directions
+        .into_iter()
+        .map(|direction| grid.sample(&grid.moisture, direction))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RESOLUTION: u32 = 8;
+    const EPSILON: f32 = 1e-3;
+
+    /// A grid with oceans, lowlands and a mountain range, and a mild climate.
+    fn test_grid() -> PlanetGrid {
+        let mut grid = PlanetGrid::new(RESOLUTION);
+        for cell in 0..grid.len() {
+            let direction = grid.direction(cell);
+            grid.elevation[cell] = 0.1 * direction.x + 0.05 * (4.0 * direction.z).sin();
+            grid.temperature[cell] = 25.0 - 20.0 * direction.y.abs();
+        }
+        grid
+    }
+
+    fn total(layer: &[f32]) -> f32 {
+        layer.iter().sum()
+    }
+
+    #[test]
+    fn only_the_ocean_evaporates() {
+        let settings = MoistureSettings {
+            saturation: f32::MAX,
+            drizzle: 0.0,
+            transport: 0.0,
+            ..default()
+        };
+        let mut grid = test_grid();
+        grid.temperature.fill(30.0);
+        settings.step(&mut grid);
+        for cell in 0..grid.len() {
+            let expected = match grid.elevation[cell] < 0.0 {
+                true => settings.evaporation,
+                false => 0.0,
+            };
+            assert_eq!(grid.moisture[cell], expected);
+        }
+    }
+
+    #[test]
+    fn advection_moves_moisture_downwind_without_losing_any() {
+        let settings = MoistureSettings {
+            evaporation: 0.0,
+            saturation: f32::MAX,
+            drizzle: 0.0,
+            orographic_lift: 0.0,
+            ..default()
+        };
+        let mut grid = test_grid();
+        let source = grid.cell_at(Vec3::new(1.0, 0.2, 0.3));
+        grid.moisture[source] = 1.0;
+        settings.step(&mut grid);
+        let downwind = downwind_cell(&grid, source);
+        assert!((grid.moisture[source] - (1.0 - settings.transport)).abs() < EPSILON);
+        assert!((grid.moisture[downwind] - settings.transport).abs() < EPSILON);
+        assert!((total(&grid.moisture) - 1.0).abs() < EPSILON);
+        assert_eq!(total(&grid.precipitation), 0.0);
+    }
+
+    #[test]
+    fn rainfall_balances_the_water_cycle() {
+        let settings = MoistureSettings {
+            precipitation_response: 1.0,
+            ..default()
+        };
+        let mut grid = test_grid();
+        grid.moisture.fill(3.0);
+        let evaporated: f32 = (0..grid.len())
+            .filter(|&cell| grid.elevation[cell] < 0.0)
+            .map(|cell| settings.evaporation * (grid.temperature[cell] / 30.0).clamp(0.0, 1.5))
+            .sum();
+        let before = total(&grid.moisture);
+        settings.step(&mut grid);
+        let rainfall = total(&grid.precipitation);
+        assert!(rainfall > 0.0);
+        assert!(
+            (before + evaporated - total(&grid.moisture) - rainfall).abs() < EPSILON * before,
+            "water was created or lost in a single day"
+        );
+    }
+
+    #[test]
+    fn warmup_is_deterministic() {
+        let settings = MoistureSettings::default();
+        let warm_up = || {
+            let mut grid = test_grid();
+            for _ in 0..WARMUP_DAYS {
+                settings.step(&mut grid);
+            }
+            grid
+        };
+        let (a, b) = (warm_up(), warm_up());
+        assert!(total(&a.moisture) > 0.0);
+        assert_eq!(a.moisture, b.moisture);
+        assert_eq!(a.precipitation, b.precipitation);
+    }
+}
@@ -1,32 +1,161 @@
 use bevy::prelude::*;
-use noise::{NoiseFn, OpenSimplex};
+use noise::{NoiseFn, OpenSimplex, Seedable};
+use serde::Deserialize;
 
-#[derive(Reflect)]
+use crate::{planet_seed::SeededRandom, tectonics::TectonicSettings};
+
+/// Where the second and third component of a domain warp are sampled, so the three
+/// components don't move in lockstep.
+const WARP_OFFSETS: [Vec3; 3] = [
+    Vec3::ZERO,
+    Vec3::new(5.2, 1.3, 7.9),
+    Vec3::new(1.7, 9.2, 3.4),
+];
+
+/// One layer of terrain. In preset files a filter is written as its settings and,
+/// optionally, its mask and blend mode.
+#[derive(Clone, Deserialize, Reflect)]
 pub(crate) struct NoiseFilter {
     #[reflect(ignore)]
+    #[serde(skip)]
     pub(crate) noise: OpenSimplex,
     pub(crate) settings: NoiseSettings,
+    /// Limits the layer to where another layer is high, like mountains to continents.
+    #[serde(default)]
+    pub(crate) mask: Option<LayerMask>,
+    /// How the layer is combined with the layers before it.
+    #[serde(default)]
+    pub(crate) blend: BlendMode,
 }
 
 impl NoiseFilter {
+    pub(crate) fn new(settings: NoiseSettings) -> Self {
+        Self {
+            noise: OpenSimplex::default(),
+            settings,
+            mask: None,
+            blend: BlendMode::Add,
+        }
+    }
+
+    pub(crate) fn seed(&self) -> u32 {
+        self.noise.seed()
+    }
+
+    /// Switches the noise to another seed. Returns whether the seed was different.
+    pub(crate) fn reseed(&mut self, seed: u32) -> bool {
+        if self.noise.seed() == seed {
+            return false;
+        }
+        self.noise = OpenSimplex::new(seed);
+        true
+    }
+
     pub(crate) fn evaluate(&self, point_on_unit_sphere: Vec3) -> f32 {
-        let mut value = 0.0;
-        let mut frequency = self.settings.base_roughness;
-        let mut amplitude = 1.0;
-        for _index in 0..self.settings.number_of_layers {
-            let point = point_on_unit_sphere * frequency + self.settings.center;
-            let noise_value = self.noise.get(point.as_dvec3().into());
-            value += noise_value.remap(-1.0, 1.0, 0.0, 1.0) * amplitude;
-            frequency *= self.settings.roughness;
-            amplitude *= self.settings.persistence;
+        match &self.settings {
+            NoiseSettings::Simple(settings) => settings.evaluate(point_on_unit_sphere, |point| {
+                self.sample(point).remap(-1.0, 1.0, 0.0, 1.0)
+            }),
+            NoiseSettings::Ridged(settings) => self.evaluate_ridged(settings, point_on_unit_sphere),
+            NoiseSettings::Billow(settings) => {
+                settings.evaluate(point_on_unit_sphere, |point| self.sample(point).abs())
+            }
+            NoiseSettings::DomainWarped(settings) => {
+                self.evaluate_domain_warped(settings, point_on_unit_sphere)
+            }
+            NoiseSettings::Crater(settings) => {
+                self.evaluate_craters(settings, point_on_unit_sphere)
+            }
+            NoiseSettings::Tectonic(settings) => {
+                settings.elevation(self.noise.seed(), point_on_unit_sphere)
+            }
+            NoiseSettings::TectonicHeat(settings) => {
+                settings.heat(self.noise.seed(), point_on_unit_sphere)
+            }
+        }
+    }
+
+    fn sample(&self, point: Vec3) -> f64 {
+        self.noise.get(point.as_dvec3().into())
+    }
+
+    /// Sharp crests where the noise crosses zero. Every octave is weighted by the ones
+    /// before it, so the detail gathers on the ridges and the valleys stay smooth.
+    fn evaluate_ridged(&self, settings: &RidgedSettings, point_on_unit_sphere: Vec3) -> f32 {
+        let mut weight = 1.0;
+        settings.fractal.evaluate(point_on_unit_sphere, |point| {
+            let ridge = 1.0 - self.sample(point).abs();
+            let value = ridge * ridge * weight;
+            weight = (value * settings.weight_multiplier).clamp(0.0, 1.0);
+            value
+        })
+    }
+
+    /// Moves the sample point by another noise field first, which bends the shapes into
+    /// swirls and ragged coastlines.
+    fn evaluate_domain_warped(&self, settings: &WarpSettings, point_on_unit_sphere: Vec3) -> f32 {
+        let warp_point = point_on_unit_sphere * settings.warp_roughness;
+        let offset =
+            Vec3::from_array(WARP_OFFSETS.map(|offset| self.sample(warp_point + offset) as f32));
+        let warped = point_on_unit_sphere + offset * settings.warp_strength;
+        settings.fractal.evaluate(warped, |point| {
+            self.sample(point).remap(-1.0, 1.0, 0.0, 1.0)
+        })
+    }
+
+    /// Bowls with raised rims, scattered over the sphere. Their places and sizes come from
+    /// the seed of the noise, so they are reproducible like the rest of the terrain.
+    fn evaluate_craters(&self, settings: &CraterSettings, point_on_unit_sphere: Vec3) -> f32 {
+        let mut elevation = 0.0;
+        let mut random = SeededRandom::new(u64::from(self.noise.seed()));
+        for _ in 0..settings.count {
+            let center = random.next_direction();
+            // Cubing makes small craters much more common than large ones.
+            let radius = settings.min_radius
+                + (settings.max_radius - settings.min_radius) * random.next_f32().powi(3);
+
+            let distance = point_on_unit_sphere.angle_between(center) / radius;
+            if distance > 1.0 + settings.rim_width {
+                continue;
+            }
+            // The bowl ends at the radius, so only the rim rises outside of it and the
+            // profile comes down to zero right at the cutoff.
+            let cavity = (distance * distance - 1.0)
+                .max(settings.floor_height - 1.0)
+                .min(0.0);
+            let rim_width = settings.rim_width.max(f32::EPSILON);
+            let rim = (1.0 - ((distance - 1.0) / rim_width).powi(2)).max(0.0) * settings.rim_height;
+            elevation += (cavity + rim) * settings.depth * radius;
         }
-        value = 0.0_f64.max(value - self.settings.min_value);
-        value as f32 * self.settings.strength
+        elevation
     }
 }
 
-#[derive(Reflect)]
-pub(crate) struct NoiseSettings {
+/// The kinds of noise a layer can be made of.
+#[derive(Clone, Deserialize, Reflect)]
+pub(crate) enum NoiseSettings {
+    /// Smooth rolling hills.
+    Simple(FractalSettings),
+    /// Mountain ranges with sharp crests.
+    Ridged(RidgedSettings),
+    /// Round humps with creases between them, like dunes.
+    Billow(FractalSettings),
+    /// Organic, swirling shapes for coastlines.
+    DomainWarped(WarpSettings),
+    /// Impact craters for barren worlds.
+    Crater(CraterSettings),
+    /// Continents, mountain ranges and rifts shaped by moving plates.
+    Tectonic(TectonicSettings),
+    /// The heat of plate boundaries and hot spots. The [`GeothermalField`](crate::GeothermalField)
+    /// adds this to its noise when the terrain has a [`NoiseSettings::Tectonic`] layer.
+    /// It is derived from that layer, so presets can't write it.
+    #[serde(skip)]
+    TectonicHeat(TectonicSettings),
+}
+
+/// Fractal noise: several octaves of noise, each with finer detail and less amplitude.
+#[derive(Clone, Deserialize, Reflect)]
+pub(crate) struct FractalSettings {
     pub(crate) number_of_layers: u32,
     pub(crate) strength: f32,
     pub(crate) base_roughness: f32,
@@ -34,5 +163,171 @@ pub(crate) struct NoiseSettings {
     pub(crate) persistence: f64,
     pub(crate) center: Vec3,
     pub(crate) min_value: f64,
-    pub(crate) use_first_layer_as_mask: bool,
+}
+
+impl FractalSettings {
+    /// Sums the octaves of `octave`, which samples noise at a point and returns a value
+    /// between zero and one.
+    fn evaluate(&self, point_on_unit_sphere: Vec3, mut octave: impl FnMut(Vec3) -> f64) -> f32 {
+        let mut value = 0.0;
+        let mut frequency = self.base_roughness;
+        let mut amplitude = 1.0;
+        for _index in 0..self.number_of_layers {
+            let point = point_on_unit_sphere * frequency + self.center;
+            value += octave(point) * amplitude;
+            frequency *= self.roughness;
+            amplitude *= self.persistence;
+        }
+        value = 0.0_f64.max(value - self.min_value);
+        value as f32 * self.strength
+    }
+}
+
+#[derive(Clone, Deserialize, Reflect)]
+pub(crate) struct RidgedSettings {
+    pub(crate) fractal: FractalSettings,
+    /// How much a ridge strengthens the detail of the next octave.
+    pub(crate) weight_multiplier: f64,
+}
+
+#[derive(Clone, Deserialize, Reflect)]
+pub(crate) struct WarpSettings {
+    pub(crate) fractal: FractalSettings,
+    /// How far points are moved, in units of the planet radius.
+    pub(crate) warp_strength: f32,
+    /// The frequency of the field that moves the points.
+    pub(crate) warp_roughness: f32,
+}
+
+#[derive(Clone, Deserialize, Reflect)]
+pub(crate) struct CraterSettings {
+    pub(crate) count: u32,
+    /// Radius of the smallest crater, as an angle in radians.
+    pub(crate) min_radius: f32,
+    pub(crate) max_radius: f32,
+    /// Depth of a crater relative to its radius.
+    pub(crate) depth: f32,
+    /// Height of the flat crater floor above the deepest point of the bowl, from `0.0` for
+    /// a round bowl to `1.0` for no bowl at all.
+    pub(crate) floor_height: f32,
+    /// Height of the rim relative to the depth.
+    pub(crate) rim_height: f32,
+    /// Width of the rim relative to the radius.
+    pub(crate) rim_width: f32,
+}
+
+/// Scales a layer by the value of another layer.
+#[derive(Clone, Deserialize, Reflect)]
+pub(crate) struct LayerMask {
+    /// Index of the masking layer. It may come before or after the masked layer.
+    pub(crate) layer: usize,
+    #[serde(default)]
+    pub(crate) curve: MaskCurve,
+}
+
+impl LayerMask {
+    /// Returns the strength of the mask given the value of the masking layer at a point.
+    /// A mask that refers to a missing layer lets everything through.
+    pub(crate) fn evaluate(&self, layer_value: Option<f32>) -> f32 {
+        layer_value.map_or(1.0, |value| self.curve.apply(value))
+    }
+}
+
+/// Turns the value of the masking layer into the strength of the mask.
+#[derive(Clone, Default, Deserialize, Reflect)]
+pub(crate) enum MaskCurve {
+    /// The value of the masking layer as it is.
+    #[default]
+    Linear,
+    /// Rises smoothly from zero to one as the masking layer goes from `from` to `to`.
+    /// With `from` above `to` the mask is inverted, e.g. to keep trenches in the ocean.
+    /// With `from` equal to `to` the mask is a hard step at that value.
+    Smoothstep { from: f32, to: f32 },
+}
+
+impl MaskCurve {
+    pub(crate) fn apply(&self, value: f32) -> f32 {
+        match *self {
+            Self::Linear => value,
+            Self::Smoothstep { from, to } if from == to => f32::from(value >= from),
+            Self::Smoothstep { from, to } => {
+                let t = ((value - from) / (to - from)).clamp(0.0, 1.0);
+                t * t * (3.0 - 2.0 * t)
+            }
+        }
+    }
+}
+
+/// How a layer is combined with the elevation of the layers before it.
+#[derive(Clone, Copy, Default, Deserialize, Reflect)]
+pub(crate) enum BlendMode {
+    /// Raises the terrain, like mountains.
+    #[default]
+    Add,
+    /// Scales the terrain, e.g. to flatten it where the layer is low.
+    Multiply,
+    /// Raises the terrain to at least the layer, like plateaus.
+    Max,
+    /// Lowers the terrain, like trenches and rifts.
+    Subtract,
+}
+
+impl BlendMode {
+    /// Blends the `value` of a layer into the `elevation`, weighted by its `mask`.
+    pub(crate) fn apply(self, elevation: f32, value: f32, mask: f32) -> f32 {
+        match self {
+            Self::Add => elevation + value * mask,
+            Self::Multiply => elevation.lerp(elevation * value, mask),
+            Self::Max => elevation.lerp(elevation.max(value), mask),
+            Self::Subtract => elevation - value * mask,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RADIUS: f32 = 0.2;
+
+    fn crater() -> CraterSettings {
+        CraterSettings {
+            count: 1,
+            min_radius: RADIUS,
+            max_radius: RADIUS,
+            depth: 0.5,
+            floor_height: 0.3,
+            rim_height: 0.2,
+            rim_width: 0.3,
+        }
+    }
+
+    #[test]
+    fn crater_profile_is_continuous_across_its_edge() {
+        let settings = crater();
+        let rim_width = settings.rim_width;
+        let filter = NoiseFilter::new(NoiseSettings::Crater(settings));
+        let center = SeededRandom::new(u64::from(filter.seed())).next_direction();
+        let axis = center.any_orthonormal_vector();
+        // Samples the crater at a distance from its center, in units of its radius.
+        let at = |distance: f32| {
+            filter.evaluate(Quat::from_axis_angle(axis, distance * RADIUS) * center)
+        };
+
+        let steps = 400;
+        let mut previous = at(0.0);
+        for step in 1..=steps {
+            let distance = 2.0 * step as f32 / steps as f32;
+            let value = at(distance);
+            assert!(
+                (value - previous).abs() < 0.005,
+                "the crater jumps from {previous} to {value} at {distance} radii"
+            );
+            previous = value;
+        }
+
+        let cutoff = 1.0 + rim_width;
+        assert!(at(cutoff - 1e-3).abs() < 1e-3);
+        assert_eq!(at(cutoff + 1e-2), 0.0);
+    }
 }
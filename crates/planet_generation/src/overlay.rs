@@ -0,0 +1,511 @@
+//! Overlays colour the planet by a quantity like its temperature or the heat of the ground.
+//!
+//! An overlay is a scalar field, sampled by a system, and a gradient that turns its values
+//! into colours. The shown overlay is sampled at every cell of the [`PlanetGrid`] into the
+//! [`OverlayData`] texture, which the [`OverlayExtension`] of the terrain and ocean materials
+//! blends over the planet. It is sampled again whenever another overlay is shown or the
+//! simulation moves on. Other crates add their own overlays with
+//! [`RegisterOverlay::register_overlay`].
+
+use bevy::{
+    asset::RenderAssetUsages,
+    color::ColorToPacked,
+    ecs::system::SystemId,
+    pbr::ExtendedMaterial,
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+};
+use common::states::Screen;
+use theme::palette::{LABEL_TEXT, NODE_BACKGROUND};
+
+use crate::{
+    controls::PlanetState,
+    overlay_material::{OverlayExtension, OverlayMaterial},
+    planet_grid::PlanetGrid,
+};
+
+/// Number of texels of a gradient that is generated from colours.
+const GRADIENT_WIDTH: u32 = 256;
+
+/// How far the opacity of the overlays moves with every key press.
+pub(crate) const OPACITY_STEP: f32 = 0.1;
+
+pub(crate) fn plugin(app: &mut App) {
+    app.register_type::<OverlaySettings>()
+        .init_resource::<OverlaySettings>()
+        .init_resource::<Overlays>()
+        .init_resource::<OverlayRepaint>()
+        .add_plugins(MaterialPlugin::<OverlayMaterial>::default())
+        .add_systems(Startup, create_textures)
+        .add_systems(OnEnter(Screen::Gameplay), spawn_legend)
+        .add_systems(
+            Update,
+            (
+                (show_overlay, update_legend),
+                paint_overlay
+                    .run_if(overlay_shown)
+                    .run_if(repaint_requested.or(resource_exists_and_changed::<PlanetGrid>)),
+            )
+                .chain()
+                .run_if(in_state(Screen::Gameplay)),
+        );
+}
+
+/// A quantity that can be shown on the planet.
+pub struct Overlay {
+    /// Shown in the legend and stored in [`PlanetState::overlay`] while the overlay is shown.
+    pub name: &'static str,
+    /// The key that shows or hides the overlay. Overlays without one are reached by cycling.
+    pub key: Option<KeyCode>,
+    /// The unit of the values, shown in the legend.
+    pub unit: &'static str,
+    pub gradient: OverlayGradient,
+}
+
+/// Turns the values of an [`Overlay`] into colours.
+pub enum OverlayGradient {
+    /// An image in the assets folder, stretched from `min` on the left to `max` on the right.
+    Image {
+        path: &'static str,
+        min: f32,
+        max: f32,
+    },
+    /// Colours at the given values, blended in between. The values have to increase.
+    Stops(Vec<(f32, Color)>),
+    /// One colour per category. The overlay's values are the indices of the categories.
+    Categories(Vec<(&'static str, Color)>),
+}
+
+impl OverlayGradient {
+    /// The values at the left and right edge of the gradient.
+    fn range(&self) -> (f32, f32) {
+        match self {
+            Self::Image { min, max, .. } => (*min, *max),
+            Self::Stops(stops) => (
+                stops.first().map_or(0.0, |&(value, _)| value),
+                stops.last().map_or(1.0, |&(value, _)| value),
+            ),
+            // Every category covers the values that round to its index.
+            Self::Categories(categories) => (-0.5, categories.len() as f32 - 0.5),
+        }
+    }
+
+    fn color_at(&self, value: f32) -> LinearRgba {
+        match self {
+            Self::Image { .. } => LinearRgba::WHITE,
+            Self::Stops(stops) => blend_stops(stops, value),
+            Self::Categories(categories) => {
+                let index = value.round().clamp(0.0, categories.len() as f32 - 1.0) as usize;
+                categories
+                    .get(index)
+                    .map_or(LinearRgba::WHITE, |(_, color)| color.to_linear())
+            }
+        }
+    }
+
+    /// Creates the texture of a gradient that is made of colours.
+    fn to_image(&self) -> Image {
+        let (min, max) = self.range();
+        let data = (0..GRADIENT_WIDTH)
+            .flat_map(|x| {
+                let value = min.lerp(max, (x as f32 + 0.5) / GRADIENT_WIDTH as f32);
+                Srgba::from(self.color_at(value)).to_u8_array()
+            })
+            .collect();
+        Image::new(
+            Extent3d {
+                width: GRADIENT_WIDTH,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            data,
+            TextureFormat::Rgba8UnormSrgb,
+            RenderAssetUsages::default(),
+        )
+    }
+}
+
+fn blend_stops(stops: &[(f32, Color)], value: f32) -> LinearRgba {
+    let upper = stops
+        .iter()
+        .position(|&(stop, _)| value < stop)
+        .unwrap_or(stops.len());
+    match upper {
+        _ if stops.is_empty() => LinearRgba::WHITE,
+        0 => stops[0].1.to_linear(),
+        upper if upper == stops.len() => stops[upper - 1].1.to_linear(),
+        upper => {
+            let (from, from_color) = stops[upper - 1];
+            let (to, to_color) = stops[upper];
+            from_color
+                .to_linear()
+                .mix(&to_color.to_linear(), (value - from) / (to - from))
+        }
+    }
+}
+
+/// All overlays, in the order they are cycled through.
+#[derive(Default, Resource)]
+pub struct Overlays(Vec<RegisteredOverlay>);
+
+struct RegisteredOverlay {
+    overlay: Overlay,
+    sampler: SystemId<In<Vec<Vec3>>, Vec<f32>>,
+    /// The gradient as a texture. Created at startup.
+    gradient: Handle<Image>,
+}
+
+impl Overlays {
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Overlay> {
+        self.0.get(index).map(|registered| &registered.overlay)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Overlay> {
+        self.0.iter().map(|registered| &registered.overlay)
+    }
+
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        self.iter().position(|overlay| overlay.name == name)
+    }
+
+    /// The overlay that follows `current` when cycling. Cycling passes through showing
+    /// no overlay at all.
+    pub(crate) fn next(&self, current: Option<usize>) -> Option<usize> {
+        match current {
+            None => (!self.is_empty()).then_some(0),
+            Some(index) => (index + 1 < self.len()).then_some(index + 1),
+        }
+    }
+
+    /// The overlay that precedes `current` when cycling.
+    pub(crate) fn previous(&self, current: Option<usize>) -> Option<usize> {
+        match current {
+            None => self.len().checked_sub(1),
+            Some(index) => index.checked_sub(1),
+        }
+    }
+}
+
+/// Adds [`Overlay`]s to an [`App`].
+pub trait RegisterOverlay {
+    /// Registers an overlay. The `sampler` is a system that returns the value of the
+    /// overlay in each of the given directions from the planet center, in the same order.
+    fn register_overlay<M>(
+        &mut self,
+        overlay: Overlay,
+        sampler: impl IntoSystem<In<Vec<Vec3>>, Vec<f32>, M> + 'static,
+    ) -> &mut Self;
+}
+
+impl RegisterOverlay for App {
+    fn register_overlay<M>(
+        &mut self,
+        overlay: Overlay,
+        sampler: impl IntoSystem<In<Vec<Vec3>>, Vec<f32>, M> + 'static,
+    ) -> &mut Self {
+        let sampler = self.register_system(sampler);
+        self.world_mut()
+            .get_resource_or_init::<Overlays>()
+            .0
+            .push(RegisteredOverlay {
+                overlay,
+                sampler,
+                gradient: Handle::default(),
+            });
+        self
+    }
+}
+
+/// How the shown overlay is drawn.
+#[derive(Reflect, Resource)]
+#[reflect(Resource)]
+pub struct OverlaySettings {
+    /// How much the overlay covers the planet, from `0.0` for not at all to `1.0`, which
+    /// hides the colours of the terrain but keeps its shading.
+    pub opacity: f32,
+}
+
+impl Default for OverlaySettings {
+    fn default() -> Self {
+        Self { opacity: 0.7 }
+    }
+}
+
+/// The values of the shown overlay, one texel per cell of the [`PlanetGrid`]. The six
+/// faces are stacked on top of each other, so the texels are in the order of the cells.
+#[derive(Resource)]
+pub(crate) struct OverlayData(Handle<Image>);
+
+impl OverlayData {
+    /// Extends a material of the planet, so it can show the overlays.
+    pub(crate) fn extend(&self, base: StandardMaterial) -> OverlayMaterial {
+        ExtendedMaterial {
+            base,
+            extension: OverlayExtension {
+                min: 0.0,
+                max: 1.0,
+                opacity: 0.0,
+                resolution: 1,
+                interpolate: 0,
+                gradient_texture: Handle::default(),
+                data_texture: self.0.clone(),
+            },
+        }
+    }
+}
+
+/// The image stays in the main world, so [`paint_overlay`] can write into it again.
+fn data_image(resolution: u32, values: &[f32]) -> Image {
+    Image::new(
+        Extent3d {
+            width: resolution,
+            height: 6 * resolution,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        values
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect(),
+        TextureFormat::R32Float,
+        RenderAssetUsages::default(),
+    )
+}
+
+/// Set when the overlay has to be sampled again.
+#[derive(Default, Resource)]
+struct OverlayRepaint(bool);
+
+/// Explains the colours of the shown overlay.
+#[derive(Component)]
+struct OverlayLegend;
+
+fn create_textures(
+    asset_server: Res<AssetServer>,
+    mut overlays: ResMut<Overlays>,
+    mut images: ResMut<Assets<Image>>,
+    mut commands: Commands,
+) {
+    for registered in &mut overlays.0 {
+        registered.gradient = match &registered.overlay.gradient {
+            OverlayGradient::Image { path, .. } => asset_server.load(*path),
+            gradient => images.add(gradient.to_image()),
+        };
+    }
+    commands.insert_resource(OverlayData(images.add(data_image(1, &[0.0; 6]))));
+}
+
+fn spawn_legend(mut commands: Commands) {
+    commands.spawn((
+        Name::new("Overlay Legend"),
+        OverlayLegend,
+        StateScoped(Screen::Gameplay),
+        Visibility::Hidden,
+        Node {
+            position_type: PositionType::Absolute,
+            left: Val::Px(10.0),
+            bottom: Val::Px(10.0),
+            flex_direction: FlexDirection::Column,
+            row_gap: Val::Px(5.0),
+            padding: UiRect::all(Val::Px(10.0)),
+            ..default()
+        },
+        BackgroundColor(NODE_BACKGROUND.with_alpha(0.8)),
+    ));
+}
+
+/// Switches the materials of the planet to the overlay in the [`PlanetState`].
+fn show_overlay(
+    states: Query<&PlanetState>,
+    changed_states: Query<(), Changed<PlanetState>>,
+    settings: Res<OverlaySettings>,
+    overlays: Res<Overlays>,
+    mut materials: ResMut<Assets<OverlayMaterial>>,
+    mut repaint: ResMut<OverlayRepaint>,
+) {
+    if changed_states.is_empty() && !settings.is_changed() {
+        return;
+    }
+    let shown = states
+        .iter()
+        .filter_map(|state| state.overlay)
+        .find_map(|name| overlays.index_of(name))
+        .map(|index| &overlays.0[index]);
+    for (_, material) in materials.iter_mut() {
+        let extension = &mut material.extension;
+        let Some(registered) = shown else {
+            extension.opacity = 0.0;
+            continue;
+        };
+        let gradient = &registered.overlay.gradient;
+        (extension.min, extension.max) = gradient.range();
+        extension.opacity = settings.opacity;
+        extension.interpolate = u32::from(!matches!(gradient, OverlayGradient::Categories(_)));
+        extension.gradient_texture = registered.gradient.clone();
+    }
+    if shown.is_some() && !changed_states.is_empty() {
+        repaint.0 = true;
+    }
+}
+
+fn update_legend(
+    states: Query<&PlanetState, Changed<PlanetState>>,
+    overlays: Res<Overlays>,
+    legend: Single<(Entity, &mut Visibility), With<OverlayLegend>>,
+    mut commands: Commands,
+) {
+    let Some(state) = states.iter().next() else {
+        return;
+    };
+    let (legend, mut visibility) = legend.into_inner();
+    commands.entity(legend).despawn_related::<Children>();
+    let Some(registered) = state
+        .overlay
+        .and_then(|name| overlays.index_of(name))
+        .map(|index| &overlays.0[index])
+    else {
+        *visibility = Visibility::Hidden;
+        return;
+    };
+    *visibility = Visibility::Inherited;
+
+    let overlay = &registered.overlay;
+    commands.entity(legend).with_children(|parent| {
+        parent.spawn(legend_text(overlay.name));
+        if let OverlayGradient::Categories(categories) = &overlay.gradient {
+            for &(name, color) in categories {
+                parent.spawn((
+                    Node {
+                        align_items: AlignItems::Center,
+                        column_gap: Val::Px(5.0),
+                        ..default()
+                    },
+                    children![
+                        (
+                            Node {
+                                width: Val::Px(16.0),
+                                height: Val::Px(16.0),
+                                ..default()
+                            },
+                            BackgroundColor(color),
+                        ),
+                        legend_text(name),
+                    ],
+                ));
+            }
+            return;
+        }
+        let (min, max) = overlay.gradient.range();
+        parent.spawn((
+            Node {
+                width: Val::Px(200.0),
+                height: Val::Px(16.0),
+                ..default()
+            },
+            ImageNode::new(registered.gradient.clone()),
+        ));
+        parent.spawn((
+            Node {
+                justify_content: JustifyContent::SpaceBetween,
+                ..default()
+            },
+            children![
+                legend_text(format_value(min, overlay.unit)),
+                legend_text(format_value(max, overlay.unit)),
+            ],
+        ));
+    });
+}
+
+fn legend_text(text: impl Into<String>) -> impl Bundle {
+    (
+        Text(text.into()),
+        TextFont::from_font_size(16.0),
+        TextColor(LABEL_TEXT),
+    )
+}
+
+fn format_value(value: f32, unit: &str) -> String {
+    match unit {
+        "" => format!("{value}"),
+        unit => format!("{value} {unit}"),
+    }
+}
+
+fn overlay_shown(states: Query<&PlanetState>) -> bool {
+    states.iter().any(|state| state.overlay.is_some())
+}
+
+fn repaint_requested(repaint: Res<OverlayRepaint>) -> bool {
+    repaint.0
+}
+
+/// Samples the shown overlay at every cell of the grid into the [`OverlayData`].
+///
+/// This needs the whole world to run the sampler, which is a system of its own.
+fn paint_overlay(world: &mut World) {
+    world.resource_mut::<OverlayRepaint>().0 = false;
+    let Ok(state) = world.query::<&PlanetState>().single(world) else {
+        return;
+    };
+    let Some(name) = state.overlay else {
+        return;
+    };
+    let overlays = world.resource::<Overlays>();
+    let Some(sampler) = overlays
+        .index_of(name)
+        .map(|index| overlays.0[index].sampler)
+    else {
+        return;
+    };
+    let Some(grid) = world.get_resource::<PlanetGrid>() else {
+        return;
+    };
+    let resolution = grid.resolution();
+    let directions: Vec<Vec3> = (0..grid.len()).map(|cell| grid.direction(cell)).collect();
+
+    let cell_count = directions.len();
+    let values = match world.run_system_with(sampler, directions) {
+        Ok(values) if values.len() == cell_count => values,
+        Ok(values) => {
+            warn!(
+                "The {name} overlay returned {} values for {cell_count} cells",
+                values.len()
+            );
+            return;
+        }
+        Err(error) => {
+            warn!("Could not sample the {name} overlay: {error}");
+            return;
+        }
+    };
+
+    let data = world.resource::<OverlayData>().0.clone();
+    let mut images = world.resource_mut::<Assets<Image>>();
+    let Some(image) = images.get_mut(&data) else {
+        warn!("The overlay data texture is gone");
+        return;
+    };
+    match image.data.as_mut() {
+        Some(data) if image.texture_descriptor.size.width == resolution => {
+            for (texel, value) in data.chunks_exact_mut(4).zip(values) {
+                texel.copy_from_slice(&value.to_le_bytes());
+            }
+        }
+        _ => *image = data_image(resolution, &values),
+    }
+    // The shader reads as many cells as the materials claim, so they only learn the new
+    // resolution along with the image. Touching them also makes them pick up the texture.
+    for (_, material) in world.resource_mut::<Assets<OverlayMaterial>>().iter_mut() {
+        material.extension.resolution = resolution;
+    }
+}
@@ -0,0 +1,42 @@
+use bevy::{
+    pbr::{ExtendedMaterial, MaterialExtension},
+    prelude::*,
+    render::render_resource::{AsBindGroup, ShaderRef},
+};
+
+/// The material of the terrain and the ocean, which can show an overlay on top of them.
+pub(crate) type OverlayMaterial = ExtendedMaterial<StandardMaterial, OverlayExtension>;
+
+/// Blends the shown overlay into the base colour of a [`StandardMaterial`], so the terrain
+/// keeps its relief and lighting underneath. Every overlay uses this extension, only the
+/// gradient, the range and the data change.
+#[derive(AsBindGroup, Asset, Clone, Debug, Reflect)]
+pub(crate) struct OverlayExtension {
+    /// The value shown at the left edge of the gradient.
+    #[uniform(100)]
+    pub min: f32,
+    /// The value shown at the right edge of the gradient.
+    #[uniform(100)]
+    pub max: f32,
+    /// How much the overlay covers the material, from `0.0` (hidden) to `1.0` (opaque).
+    #[uniform(100)]
+    pub opacity: f32,
+    /// Number of cells along each edge of a face of the [`PlanetGrid`](crate::PlanetGrid).
+    #[uniform(100)]
+    pub resolution: u32,
+    /// Whether values are blended between neighbouring cells. Categories must not be.
+    #[uniform(100)]
+    pub interpolate: u32,
+    #[texture(101)]
+    #[sampler(102)]
+    pub gradient_texture: Handle<Image>,
+    /// One value per cell of the planet grid. See [`OverlayData`](crate::overlay::OverlayData).
+    #[texture(103, sample_type = "float", filterable = false)]
+    pub data_texture: Handle<Image>,
+}
+
+impl MaterialExtension for OverlayExtension {
+    fn fragment_shader() -> ShaderRef {
+        "shaders/overlay.wgsl".into()
+    }
+}
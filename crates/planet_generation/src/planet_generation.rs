@@ -1,77 +1,128 @@
-use bevy::{color::palettes::css::BLUE, prelude::*};
-use common::states::Screen;
+use bevy::prelude::*;
+use common::{states::Screen, system_sets::AppSet};
 use leafwing_input_manager::plugin::InputManagerPlugin;
-use noise::OpenSimplex;
 
 use crate::{
+    biome, climate,
     controls::{self, PlanetActions},
-    geothermal_material::GeothermalMaterial,
-    noise_filter::{NoiseFilter, NoiseSettings},
+    erosion::{self, ErosionTask},
+    geothermal_field::{GEOTHERMAL_OVERLAY, GeothermalField, sample_heat},
+    mesh_generation::{self, MeshGeneration, PlanetPlaceholder, finish_mesh_generation},
+    moisture,
+    overlay::{self, Overlay, OverlayData, OverlayGradient, RegisterOverlay},
+    overlay_material::OverlayMaterial,
+    planet_grid::PlanetGrid,
+    planet_preset, planet_seed,
     planet_settings::PlanetSettings,
-    terrain_face::TerrainFace,
+    planet_surface::SurfaceSample,
+    tectonics,
+    terrain_chunk::{self, TerrainChunk, TerrainMaterial},
+    terrain_face::{DIRECTIONS, TerrainFace},
 };
 
+/// How long [`PlanetSettings`] have to stay untouched before the planet is regenerated, so
+/// dragging a slider in the inspector doesn't rebuild the terrain every frame.
+const REGENERATION_DELAY_SECS: f32 = 0.3;
+
 #[derive(Component)]
-pub(crate) struct Planet;
+pub struct Planet;
 
 #[derive(Component)]
-pub(crate) struct GeothermalOverlay;
+pub(crate) struct Ocean;
+
+/// The terrain meshes and the [`PlanetGrid`] are waiting to be built, once the terrain has
+/// been eroded.
+#[derive(Resource)]
+pub(crate) struct PendingTerrain;
 
 pub fn plugin(app: &mut App) {
     app.register_type::<PlanetSettings>()
+        .register_type::<GeothermalField>()
+        .register_type::<PlanetGrid>()
         .register_type::<TerrainFace>()
-        .insert_resource(
-            PlanetSettings {
-                resolution: 100,
-                color: BLUE.into(),
-                radius: 2.0,
-                ..default()
-            }
-            .with_layer(NoiseFilter {
-                noise: OpenSimplex::new(0),
-                settings: NoiseSettings {
-                    number_of_layers: 5,
-                    strength: 0.2,
-                    base_roughness: 0.71,
-                    roughness: 1.81,
-                    persistence: 0.54,
-                    center: Vec3::ZERO,
-                    min_value: 1.1,
-                    use_first_layer_as_mask: false,
+        .register_type::<SurfaceSample>()
+        .register_type::<RegenerationTimer>()
+        .init_resource::<PlanetSettings>()
+        .init_resource::<GeothermalField>()
+        .register_overlay(
+            Overlay {
+                name: GEOTHERMAL_OVERLAY,
+                key: Some(KeyCode::KeyG),
+                unit: "",
+                gradient: OverlayGradient::Image {
+                    path: "textures/thermal_gradient.png",
+                    min: 0.0,
+                    max: 1.0,
                 },
-            })
-            .with_layer(NoiseFilter {
-                noise: OpenSimplex::new(0),
-                settings: NoiseSettings {
-                    number_of_layers: 5,
-                    strength: 10.0,
-                    base_roughness: 1.08,
-                    roughness: 2.34,
-                    persistence: 0.53,
-                    center: Vec3::ZERO,
-                    min_value: 1.2,
-                    use_first_layer_as_mask: true,
-                },
-            }),
+            },
+            sample_heat,
         )
-        .add_plugins(MaterialPlugin::<GeothermalMaterial>::default())
         .add_plugins(InputManagerPlugin::<PlanetActions>::default())
+        .add_plugins((
+            overlay::plugin,
+            mesh_generation::plugin,
+            terrain_chunk::plugin,
+            climate::plugin,
+            erosion::plugin,
+            moisture::plugin,
+            biome::plugin,
+            planet_preset::plugin,
+            planet_seed::plugin,
+            tectonics::plugin,
+        ))
         .add_systems(OnEnter(Screen::Gameplay), (spawn_planet, controls::setup))
-        .add_systems(Update, controls::check.run_if(in_state(Screen::Gameplay)));
+        .add_systems(
+            Update,
+            (
+                controls::check,
+                schedule_regeneration,
+                tick_regeneration_timer
+                    .run_if(resource_exists::<RegenerationTimer>)
+                    .in_set(AppSet::TickTimers),
+                (
+                    regenerate_planet.run_if(regeneration_due),
+                    generate_terrain.run_if(resource_exists::<PendingTerrain>),
+                )
+                    .chain()
+                    .after(finish_mesh_generation)
+                    .in_set(AppSet::Update),
+            )
+                .run_if(in_state(Screen::Gameplay)),
+        );
+}
+
+#[derive(Resource, Debug, Clone, PartialEq, Reflect)]
+#[reflect(Resource)]
+struct RegenerationTimer(Timer);
+
+impl Default for RegenerationTimer {
+    fn default() -> Self {
+        Self(Timer::from_seconds(
+            REGENERATION_DELAY_SECS,
+            TimerMode::Once,
+        ))
+    }
 }
 
 fn spawn_planet(
-    asset_server: Res<AssetServer>,
     settings: Res<PlanetSettings>,
+    overlay_data: Res<OverlayData>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
-    mut geothermal_material: ResMut<Assets<GeothermalMaterial>>,
+    mut overlay_materials: ResMut<Assets<OverlayMaterial>>,
     mut commands: Commands,
 ) {
     // light
     commands.spawn((PointLight::default(), Transform::from_xyz(10.0, 0.0, 2.0)));
 
-    let material_handle = materials.add(settings.color);
+    // Systems rely on the grid from the start. It is rebuilt along with the terrain.
+    commands.insert_resource(PlanetGrid::from_settings(&settings));
+    start_terrain(&settings, &mut commands);
+
+    // The terrain is tinted by its biomes through vertex colours, which are multiplied with
+    // the base colour. Overlays are blended over both.
+    let material_handle = overlay_materials.add(overlay_data.extend(Color::WHITE.into()));
+    commands.insert_resource(TerrainMaterial(material_handle.clone()));
 
     commands
         .spawn((
@@ -81,78 +132,113 @@ fn spawn_planet(
             Planet,
         ))
         .with_children(|parent| {
-            let directions = [
-                Vec3::X,
-                Vec3::Y,
-                Vec3::Z,
-                Vec3::NEG_X,
-                Vec3::NEG_Y,
-                Vec3::NEG_Z,
-            ];
-            for local_up in directions {
-                let terrain_face = TerrainFace::new(local_up);
-                let mesh_handle = meshes.add(terrain_face.to_mesh(&settings));
+            for local_up in DIRECTIONS {
                 parent.spawn((
-                    terrain_face,
-                    Mesh3d(mesh_handle),
+                    TerrainFace::new(local_up),
+                    TerrainChunk::default(),
                     MeshMaterial3d(material_handle.clone()),
                 ));
             }
+            parent.spawn((
+                Name::new("Placeholder"),
+                PlanetPlaceholder,
+                Mesh3d(meshes.add(Sphere::new(settings.radius).mesh().ico(5).unwrap())),
+                MeshMaterial3d(materials.add(settings.color)),
+            ));
         });
 
-    let geothermal_settings = PlanetSettings { radius: settings.radius, ..default() }.with_layer(NoiseFilter {
-        noise: OpenSimplex::new(0),
-        settings: NoiseSettings {
-            number_of_layers: 1,
-            strength: 1.0,
-            base_roughness: 2.0,
-            roughness: 1.0,
-            persistence: 0.0,
-            center: Vec3::ZERO,
-            min_value: 0.0,
-            use_first_layer_as_mask: false,
-        },
-    });
-    commands
-        .spawn((
-            Transform::default(),
-            Visibility::Hidden,
-            Name::new("Geothermal Overlay"),
-            GeothermalOverlay,
-        ))
-        .with_children(|parent| {
-            let directions = [
-                Vec3::X,
-                Vec3::Y,
-                Vec3::Z,
-                Vec3::NEG_X,
-                Vec3::NEG_Y,
-                Vec3::NEG_Z,
-            ];
-            for local_up in directions {
-                let terrain_face = TerrainFace::new(local_up);
-                let mesh_handle = meshes.add(terrain_face.to_mesh(&geothermal_settings));
-                let geothermal_material_handle = geothermal_material.add(GeothermalMaterial {
-                    radius: geothermal_settings.radius,
-                    gradient_texture: asset_server.load("textures/thermal_gradient.png"),
-                });
-                parent.spawn((
-                    terrain_face,
-                    Mesh3d(mesh_handle),
-                    MeshMaterial3d(geothermal_material_handle.clone()),
-                ));
-            }
-        });
+    commands.spawn((
+        Transform::default(),
+        Visibility::Inherited,
+        Name::new("Ocean"),
+        Ocean,
+        Mesh3d(meshes.add(ocean_mesh(&settings))),
+        MeshMaterial3d(overlay_materials.add(overlay_data.extend(StandardMaterial {
+            base_color: settings.ocean_color,
+            alpha_mode: AlphaMode::Blend,
+            perceptual_roughness: 0.1,
+            ..default()
+        }))),
+    ));
+}
+
+fn ocean_mesh(settings: &PlanetSettings) -> Mesh {
+    let ocean_radius = settings.radius * (1.0 + settings.sea_level);
+    Sphere::new(ocean_radius).mesh().ico(6).unwrap()
 }
 
-// fn update_face(
-//     settings: Res<PlanetSettings>,
-//     mut meshes: ResMut<Assets<Mesh>>,
-//     query: Query<(Entity, &TerrainFace)>,
-//     mut commands: Commands,
-// ) {
-//     for (entity, face) in &query {
-//         let mesh_handle = meshes.add(face.to_mesh(&settings));
-//         commands.entity(entity).insert(Mesh3d(mesh_handle));
-//     }
-// }
+/// Restarts the regeneration delay whenever the settings are edited.
+fn schedule_regeneration(
+    settings: Res<PlanetSettings>,
+    geothermal_field: Res<GeothermalField>,
+    mut commands: Commands,
+) {
+    let edited = (settings.is_changed() && !settings.is_added())
+        || (geothermal_field.is_changed() && !geothermal_field.is_added());
+    if edited {
+        commands.insert_resource(RegenerationTimer::default());
+    }
+}
+
+fn tick_regeneration_timer(time: Res<Time>, mut timer: ResMut<RegenerationTimer>) {
+    timer.0.tick(time.delta());
+}
+
+fn regeneration_due(timer: Option<Res<RegenerationTimer>>) -> bool {
+    timer.is_some_and(|timer| timer.0.just_finished())
+}
+
+/// Rebuilds the ocean from the current settings and starts over with the terrain.
+fn regenerate_planet(
+    settings: Res<PlanetSettings>,
+    ocean: Single<(&Mesh3d, &MeshMaterial3d<OverlayMaterial>), With<Ocean>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<OverlayMaterial>>,
+    mut commands: Commands,
+) {
+    commands.remove_resource::<RegenerationTimer>();
+    start_terrain(&settings, &mut commands);
+
+    let (ocean_mesh_handle, ocean_material) = ocean.into_inner();
+    if let Some(mesh) = meshes.get_mut(ocean_mesh_handle) {
+        *mesh = ocean_mesh(&settings);
+    }
+    if let Some(material) = materials.get_mut(ocean_material) {
+        material.base.base_color = settings.ocean_color;
+    }
+}
+
+/// Erodes the terrain on another thread, if it has erosion, and has the meshes and the grid
+/// built afterwards by [`generate_terrain`].
+fn start_terrain(settings: &PlanetSettings, commands: &mut Commands) {
+    match ErosionTask::start(settings) {
+        Some(erosion) => commands.insert_resource(erosion),
+        None => commands.remove_resource::<ErosionTask>(),
+    }
+    commands.insert_resource(PendingTerrain);
+}
+
+/// Builds the terrain meshes and the grid once the erosion has finished. Faces keep showing
+/// their old mesh until the new one has been generated into the same asset.
+fn generate_terrain(
+    erosion: Option<Res<ErosionTask>>,
+    settings: Res<PlanetSettings>,
+    faces: Query<(Entity, &TerrainFace, &TerrainChunk)>,
+    mut commands: Commands,
+) {
+    if erosion.is_some() {
+        return;
+    }
+    commands.remove_resource::<PendingTerrain>();
+
+    for (entity, face, chunk) in &faces {
+        commands
+            .entity(entity)
+            .insert(MeshGeneration::start(*face, *chunk, settings.clone()));
+    }
+
+    // Replace the grid instead of overwriting it, so the climate starts over from the new
+    // terrain as if the planet had just been spawned.
+    commands.remove_resource::<PlanetGrid>();
+    commands.insert_resource(PlanetGrid::from_settings(&settings));
+}
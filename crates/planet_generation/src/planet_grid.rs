@@ -0,0 +1,207 @@
+//! A grid of tiles covering the planet, used to store per-location simulation state.
+//!
+//! The grid uses the same cube-sphere parameterization as the [`TerrainFace`]s: every face
+//! is split into `resolution * resolution` cells, and cells are addressed by a single index.
+
+use bevy::prelude::*;
+
+use crate::{
+    biome::Biome,
+    planet_settings::PlanetSettings,
+    terrain_face::{DIRECTIONS, TerrainFace},
+};
+
+/// Per-tile simulation state on a cube-sphere grid.
+///
+/// Every layer stores one value per cell, indexed the same way as [`PlanetGrid::direction`].
+#[derive(Reflect, Resource)]
+#[reflect(Resource)]
+pub struct PlanetGrid {
+    resolution: u32,
+    #[reflect(ignore)]
+    directions: Vec<Vec3>,
+    #[reflect(ignore)]
+    neighbours: Vec<[usize; 4]>,
+    /// Terrain height above sea level in world units. Negative values lie underwater.
+    #[reflect(ignore)]
+    pub elevation: Vec<f32>,
+    #[reflect(ignore)]
+    pub temperature: Vec<f32>,
+    /// Water vapour carried by the air above a cell.
+    #[reflect(ignore)]
+    pub moisture: Vec<f32>,
+    /// Smoothed amount of rain falling on a cell per day.
+    #[reflect(ignore)]
+    pub precipitation: Vec<f32>,
+    #[reflect(ignore)]
+    pub soil: Vec<f32>,
+    #[reflect(ignore)]
+    pub biomass: Vec<f32>,
+    #[reflect(ignore)]
+    pub biome: Vec<Biome>,
+    /// Index of the tectonic plate a cell belongs to. Zero everywhere if the terrain has
+    /// no tectonic layer.
+    #[reflect(ignore)]
+    pub plate: Vec<u32>,
+}
+
+impl PlanetGrid {
+    /// Creates a grid with `resolution * resolution` cells per face and all layers set to zero.
+    pub fn new(resolution: u32) -> Self {
+        let resolution = resolution.max(1);
+        let faces = DIRECTIONS.map(TerrainFace::new);
+        let cell_uv = |x: u32, y: u32| (UVec2::new(x, y).as_vec2() + 0.5) / resolution as f32;
+
+        let mut directions = Vec::with_capacity(6 * (resolution * resolution) as usize);
+        for face in &faces {
+            for y in 0..resolution {
+                for x in 0..resolution {
+                    directions.push(face.point_on_unit_sphere(cell_uv(x, y)));
+                }
+            }
+        }
+
+        let step = 1.0 / resolution as f32;
+        let mut neighbours = Vec::with_capacity(directions.len());
+        for face in &faces {
+            for y in 0..resolution {
+                for x in 0..resolution {
+                    let uv = cell_uv(x, y);
+                    // Stepping off a face lands on the plane of the cube side beyond its
+                    // edge. Projecting that point back onto the sphere finds the matching
+                    // cell on the adjacent face.
+                    neighbours.push(
+                        [
+                            Vec2::new(-step, 0.0),
+                            Vec2::new(step, 0.0),
+                            Vec2::new(0.0, -step),
+                            Vec2::new(0.0, step),
+                        ]
+                        .map(|offset| {
+                            let direction = face.point_on_unit_cube(uv + offset).normalize();
+                            cell_index(resolution, direction)
+                        }),
+                    );
+                }
+            }
+        }
+
+        let len = directions.len();
+        Self {
+            resolution,
+            directions,
+            neighbours,
+            elevation: vec![0.0; len],
+            temperature: vec![0.0; len],
+            moisture: vec![0.0; len],
+            precipitation: vec![0.0; len],
+            soil: vec![0.0; len],
+            biomass: vec![0.0; len],
+            biome: vec![Biome::default(); len],
+            plate: vec![0; len],
+        }
+    }
+
+    /// Creates a grid and fills its [`PlanetGrid::elevation`] and [`PlanetGrid::plate`]
+    /// layers from the terrain.
+    pub(crate) fn from_settings(settings: &PlanetSettings) -> Self {
+        let mut grid = Self::new(settings.grid_resolution);
+        for (elevation, direction) in grid.elevation.iter_mut().zip(&grid.directions) {
+            *elevation =
+                (settings.calculate_elevation(*direction) - settings.sea_level) * settings.radius;
+        }
+        if let Some((layer, tectonics)) = settings.tectonics() {
+            for (plate, direction) in grid.plate.iter_mut().zip(&grid.directions) {
+                *plate = tectonics.plate_at(layer.seed(), *direction);
+            }
+        }
+        grid
+    }
+
+    /// Number of cells along one edge of a face.
+    pub fn resolution(&self) -> u32 {
+        self.resolution
+    }
+
+    /// Total number of cells.
+    pub fn len(&self) -> usize {
+        self.directions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.directions.is_empty()
+    }
+
+    /// Unit direction from the planet center towards the center of a cell.
+    pub fn direction(&self, cell: usize) -> Vec3 {
+        self.directions[cell]
+    }
+
+    /// The cell that contains the given direction.
+    pub fn cell_at(&self, direction: Vec3) -> usize {
+        cell_index(self.resolution, direction)
+    }
+
+    /// The four cells that share an edge with the given cell, also across face boundaries.
+    pub fn neighbours(&self, cell: usize) -> [usize; 4] {
+        self.neighbours[cell]
+    }
+
+    /// Looks up the value of a layer in the given direction.
+    pub fn sample(&self, layer: &[f32], direction: Vec3) -> f32 {
+        layer[self.cell_at(direction)]
+    }
+}
+
+fn cell_index(resolution: u32, direction: Vec3) -> usize {
+    let face = TerrainFace::containing(direction);
+    let face_index = DIRECTIONS
+        .iter()
+        .position(|&local_up| local_up == face.local_up())
+        .unwrap();
+    let cell = (face.uv_of(direction) * resolution as f32)
+        .as_uvec2()
+        .min(UVec2::splat(resolution - 1));
+    (face_index as u32 * resolution * resolution + cell.y * resolution + cell.x) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RESOLUTION: u32 = 8;
+
+    #[test]
+    fn neighbours_are_symmetric_across_face_edges() {
+        let grid = PlanetGrid::new(RESOLUTION);
+        let last = RESOLUTION - 1;
+        for face in 0..6 {
+            for y in 0..RESOLUTION {
+                for x in 0..RESOLUTION {
+                    if x != 0 && y != 0 && x != last && y != last {
+                        continue;
+                    }
+                    let cell = (face * RESOLUTION * RESOLUTION + y * RESOLUTION + x) as usize;
+                    let neighbours = grid.neighbours(cell);
+                    for neighbour in neighbours {
+                        assert_ne!(
+                            neighbour, cell,
+                            "cell {cell} on face {face} is its own neighbour"
+                        );
+                        assert!(
+                            grid.neighbours(neighbour).contains(&cell),
+                            "cell {cell} at ({x}, {y}) on face {face} lists {neighbour} as a \
+                             neighbour, but not the other way around"
+                        );
+                    }
+                    for (i, a) in neighbours.iter().enumerate() {
+                        assert!(
+                            !neighbours[i + 1..].contains(a),
+                            "cell {cell} at ({x}, {y}) on face {face} lists {a} twice"
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,171 @@
+//! Planet presets are RON files in `assets/planets`, so new kinds of planets can be
+//! designed without recompiling. `planets.catalog.ron` lists the presets the game offers.
+//!
+//! Editing a preset while the game runs applies it right away, and the planet is
+//! regenerated from the new settings.
+
+use asset_tracking::LoadResource;
+use bevy::{
+    asset::{AssetLoader, LoadContext, io::Reader},
+    prelude::*,
+};
+use serde::Deserialize;
+
+use crate::{
+    climate::ClimateSettings, geothermal_field::GeothermalField, noise_filter::NoiseSettings,
+    planet_settings::PlanetSettings,
+};
+
+pub(crate) fn plugin(app: &mut App) {
+    app.init_asset::<PlanetPreset>()
+        .init_asset::<PlanetCatalog>()
+        .init_asset_loader::<PlanetPresetLoader>()
+        .init_asset_loader::<PlanetCatalogLoader>()
+        .register_type::<PlanetPresets>()
+        .load_resource::<PlanetPresets>()
+        .add_systems(
+            Update,
+            apply_planet_preset.run_if(resource_exists::<PlanetPresets>),
+        );
+}
+
+/// Everything that makes up one kind of planet.
+#[derive(Asset, Clone, Deserialize, TypePath)]
+pub struct PlanetPreset {
+    pub(crate) planet: PlanetSettings,
+    pub(crate) geothermal: NoiseSettings,
+    #[serde(default)]
+    pub(crate) climate: ClimateSettings,
+}
+
+/// The presets listed in a `.catalog.ron` file, in order.
+#[derive(Asset, TypePath)]
+pub struct PlanetCatalog {
+    #[dependency]
+    presets: Vec<Handle<PlanetPreset>>,
+}
+
+/// The available planet presets and the one new planets are made from.
+#[derive(Resource, Asset, Clone, Reflect)]
+#[reflect(Resource)]
+pub struct PlanetPresets {
+    #[dependency]
+    catalog: Handle<PlanetCatalog>,
+    /// Index of the active preset in the catalog.
+    pub selected: usize,
+}
+
+impl FromWorld for PlanetPresets {
+    fn from_world(world: &mut World) -> Self {
+        let assets = world.resource::<AssetServer>();
+        Self {
+            catalog: assets.load("planets/planets.catalog.ron"),
+            selected: 0,
+        }
+    }
+}
+
+type LoadError = Box<dyn std::error::Error + Send + Sync>;
+
+#[derive(Default)]
+struct PlanetPresetLoader;
+
+impl AssetLoader for PlanetPresetLoader {
+    type Asset = PlanetPreset;
+    type Settings = ();
+    type Error = LoadError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<PlanetPreset, LoadError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(ron::de::from_bytes(&bytes)?)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["planet.ron"]
+    }
+}
+
+/// The contents of a `.catalog.ron` file: asset paths of the presets.
+#[derive(Deserialize)]
+struct CatalogFile {
+    presets: Vec<String>,
+}
+
+#[derive(Default)]
+struct PlanetCatalogLoader;
+
+impl AssetLoader for PlanetCatalogLoader {
+    type Asset = PlanetCatalog;
+    type Settings = ();
+    type Error = LoadError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        load_context: &mut LoadContext<'_>,
+    ) -> Result<PlanetCatalog, LoadError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let file: CatalogFile = ron::de::from_bytes(&bytes)?;
+        Ok(PlanetCatalog {
+            presets: file
+                .presets
+                .into_iter()
+                .map(|path| load_context.load(path))
+                .collect(),
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["catalog.ron"]
+    }
+}
+
+/// Copies the selected preset into the settings resources whenever the selection changes
+/// or one of the files is reloaded.
+pub(crate) fn apply_planet_preset(
+    presets: Res<PlanetPresets>,
+    catalogs: Res<Assets<PlanetCatalog>>,
+    preset_assets: Res<Assets<PlanetPreset>>,
+    (mut catalog_events, mut preset_events): (
+        EventReader<AssetEvent<PlanetCatalog>>,
+        EventReader<AssetEvent<PlanetPreset>>,
+    ),
+    mut planet_settings: ResMut<PlanetSettings>,
+    mut geothermal_field: ResMut<GeothermalField>,
+    mut climate_settings: ResMut<ClimateSettings>,
+) {
+    let Some(handle) = catalogs
+        .get(&presets.catalog)
+        .and_then(|catalog| catalog.presets.get(presets.selected))
+    else {
+        warn_once!("Planet preset {} is not in the catalog", presets.selected);
+        return;
+    };
+    let catalog_reloaded = catalog_events
+        .read()
+        .filter(|event| event.is_modified(&presets.catalog))
+        .count()
+        > 0;
+    let preset_reloaded = preset_events
+        .read()
+        .filter(|event| event.is_modified(handle))
+        .count()
+        > 0;
+    if !presets.is_changed() && !catalog_reloaded && !preset_reloaded {
+        return;
+    }
+    let Some(preset) = preset_assets.get(handle) else {
+        return;
+    };
+    *planet_settings = preset.planet.clone();
+    geothermal_field.filter.settings = preset.geothermal.clone();
+    *climate_settings = preset.climate.clone();
+}
@@ -0,0 +1,243 @@
+//! The number a planet is generated from. Every noise layer and every subsystem derives
+//! its own seed from the [`PlanetSeed`], so they don't repeat each other's patterns, and the
+//! same planet seed always produces exactly the same planet.
+
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+use common::states::Screen;
+
+use crate::{
+    geothermal_field::GeothermalField, planet_preset::apply_planet_preset,
+    planet_settings::PlanetSettings,
+};
+
+pub(crate) fn plugin(app: &mut App) {
+    app.register_type::<PlanetSeed>()
+        .init_resource::<PlanetSeed>()
+        .add_systems(OnEnter(Screen::Gameplay), log_planet_seed)
+        .add_systems(Update, apply_planet_seed.after(apply_planet_preset));
+}
+
+/// The seed of the current planet. Keep it around to regenerate the planet exactly.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Reflect, Resource)]
+#[reflect(Resource)]
+pub struct PlanetSeed(pub u32);
+
+/// The parts of the generation that draw their own random numbers.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SeedStream {
+    Terrain,
+    Geothermal,
+    Climate,
+    Erosion,
+}
+
+impl PlanetSeed {
+    /// Turns some entropy, like the time a button was pressed, into a seed.
+    pub fn from_entropy(entropy: u64) -> Self {
+        Self(split_mix(entropy) as u32)
+    }
+
+    /// Returns the seed for one layer of a subsystem. It only depends on the planet seed,
+    /// so adding a layer doesn't change the ones before it.
+    pub fn derive(self, stream: SeedStream, layer: u32) -> u32 {
+        let stream = split_mix(u64::from(self.0) ^ ((stream as u64) << 32));
+        split_mix(stream ^ u64::from(layer)) as u32
+    }
+}
+
+/// The SplitMix64 finalizer. Every input bit affects every output bit, and unlike the
+/// hashers in `std` the result is the same on every platform and Rust version.
+fn split_mix(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// A small random number generator for placing things like craters and plates. The same
+/// seed always yields the same sequence.
+pub(crate) struct SeededRandom(u64);
+
+impl SeededRandom {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    /// Returns a number between zero and one.
+    pub(crate) fn next_f32(&mut self) -> f32 {
+        self.0 = split_mix(self.0);
+        (self.0 >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// Returns a direction, uniformly distributed over the sphere.
+    pub(crate) fn next_direction(&mut self) -> Vec3 {
+        let z = self.next_f32() * 2.0 - 1.0;
+        let (sin, cos) = (self.next_f32() * TAU).sin_cos();
+        Vec2::new(cos, sin).extend(0.0) * (1.0 - z * z).sqrt() + Vec3::Z * z
+    }
+}
+
+/// Seeds the noise of the terrain and the geothermal field. Presets don't carry seeds, so
+/// this also runs right after a preset has been applied.
+///
+/// The resources are only marked as changed if a seed actually differs, otherwise the
+/// planet would be regenerated every frame.
+pub(crate) fn apply_planet_seed(
+    seed: Res<PlanetSeed>,
+    mut settings: ResMut<PlanetSettings>,
+    mut geothermal_field: ResMut<GeothermalField>,
+) {
+    if settings.bypass_change_detection().reseed(*seed) {
+        settings.set_changed();
+    }
+    let geothermal_seed = seed.derive(SeedStream::Geothermal, 0);
+    if geothermal_field
+        .bypass_change_detection()
+        .filter
+        .reseed(geothermal_seed)
+    {
+        geothermal_field.set_changed();
+    }
+}
+
+fn log_planet_seed(seed: Res<PlanetSeed>) {
+    info!("Generating planet with seed {}", seed.0);
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::render::mesh::VertexAttributeValues;
+
+    use super::*;
+    use crate::{
+        noise_filter::{FractalSettings, NoiseFilter, NoiseSettings},
+        terrain_chunk::TerrainChunk,
+        terrain_face::{DIRECTIONS, TerrainFace},
+    };
+
+    fn settings(seed: PlanetSeed) -> PlanetSettings {
+        let layer = || {
+            NoiseFilter::new(NoiseSettings::Simple(FractalSettings {
+                number_of_layers: 4,
+                strength: 0.3,
+                base_roughness: 1.5,
+                roughness: 2.0,
+                persistence: 0.5,
+                center: Vec3::ZERO,
+                min_value: 0.8,
+            }))
+        };
+        let mut settings = PlanetSettings {
+            resolution: 9,
+            ..default()
+        }
+        .with_layer(layer())
+        .with_layer(layer());
+        settings.reseed(seed);
+        settings
+    }
+
+    fn positions(settings: &PlanetSettings) -> Vec<[f32; 3]> {
+        let mut all = Vec::new();
+        for local_up in DIRECTIONS {
+            let mesh = TerrainFace::new(local_up).to_mesh(settings, TerrainChunk::default());
+            let Some(VertexAttributeValues::Float32x3(positions)) =
+                mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+            else {
+                panic!("terrain mesh has no positions");
+            };
+            all.extend_from_slice(positions);
+        }
+        all
+    }
+
+    fn to_bits(positions: &[[f32; 3]]) -> Vec<[u32; 3]> {
+        positions
+            .iter()
+            .map(|position| position.map(f32::to_bits))
+            .collect()
+    }
+
+    /// These values must never change, or saved seeds would produce different planets.
+    #[test]
+    fn derived_seeds_are_stable() {
+        let zero = PlanetSeed(0);
+        assert_eq!(zero.derive(SeedStream::Terrain, 0), 1_293_516_399);
+        assert_eq!(zero.derive(SeedStream::Terrain, 1), 3_365_057_806);
+        assert_eq!(zero.derive(SeedStream::Geothermal, 0), 996_191_566);
+        assert_eq!(zero.derive(SeedStream::Climate, 0), 3_077_866_768);
+        let seed = PlanetSeed(42);
+        assert_eq!(seed.derive(SeedStream::Terrain, 0), 1_695_576_580);
+        assert_eq!(seed.derive(SeedStream::Terrain, 1), 2_358_461_157);
+        assert_eq!(seed.derive(SeedStream::Geothermal, 0), 1_568_497_524);
+        assert_eq!(seed.derive(SeedStream::Climate, 0), 3_683_893_850);
+    }
+
+    #[test]
+    fn layers_and_subsystems_get_distinct_seeds() {
+        let seed = PlanetSeed(7);
+        let mut seeds = Vec::new();
+        for stream in [
+            SeedStream::Terrain,
+            SeedStream::Geothermal,
+            SeedStream::Climate,
+        ] {
+            for layer in 0..8 {
+                seeds.push(seed.derive(stream, layer));
+            }
+        }
+        let count = seeds.len();
+        seeds.sort_unstable();
+        seeds.dedup();
+        assert_eq!(seeds.len(), count);
+    }
+
+    #[test]
+    fn same_seed_yields_identical_vertices() {
+        let first = positions(&settings(PlanetSeed(1234)));
+        let second = positions(&settings(PlanetSeed(1234)));
+        assert_eq!(to_bits(&first), to_bits(&second));
+    }
+
+    /// A sample of the vertices a fixed seed produced when the seeds were introduced. If this
+    /// fails, saved seeds no longer regenerate the planets they were saved for.
+    #[test]
+    fn seed_yields_the_golden_vertices() {
+        const GOLDEN: [(usize, [u32; 3]); 13] = [
+            (0, [1_058_695_381, 1_058_695_381, 3_206_179_029]),
+            (58, [1_064_043_633, 3_203_138_673, 0]),
+            (116, [1_058_211_064, 1_058_211_064, 3_205_694_712]),
+            (174, [3_195_248_715, 1_064_542_283, 3_203_637_323]),
+            (232, [3_207_438_133, 1_059_954_485, 1_057_109_864]),
+            (290, [3_202_585_071, 3_202_585_071, 1_063_490_031]),
+            (348, [1_051_948_302, 3_207_820_558, 1_060_336_910]),
+            (406, [3_210_362_322, 3_201_973_714, 1_059_303_005]),
+            (464, [3_207_543_713, 1_043_282_849, 1_060_060_065]),
+            (522, [1_061_133_540, 3_208_617_188, 3_200_228_580]),
+            (580, [1_060_981_895, 3_208_465_543, 0]),
+            (638, [3_192_075_749, 3_208_852_965, 3_208_852_965]),
+            (696, [3_191_004_130, 1_060_297_698, 3_207_781_346]),
+        ];
+        let vertices = to_bits(&positions(&settings(PlanetSeed(1234))));
+        assert_eq!(vertices.len(), 702);
+        for (index, expected) in GOLDEN {
+            assert_eq!(vertices[index], expected, "vertex {index} moved");
+        }
+    }
+
+    #[test]
+    fn different_seeds_yield_different_terrain() {
+        let first = positions(&settings(PlanetSeed(1)));
+        let second = positions(&settings(PlanetSeed(2)));
+        assert_ne!(to_bits(&first), to_bits(&second));
+    }
+
+    #[test]
+    fn reseeding_with_the_same_seed_changes_nothing() {
+        let mut settings = settings(PlanetSeed(5));
+        assert!(!settings.reseed(PlanetSeed(5)));
+        assert!(settings.reseed(PlanetSeed(6)));
+    }
+}
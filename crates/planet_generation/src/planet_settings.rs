@@ -1,38 +1,142 @@
 use bevy::{color::palettes::css::BLUE, prelude::*};
 use bevy_inspector_egui::InspectorOptions;
 use bevy_inspector_egui::prelude::ReflectInspectorOptions;
+use serde::Deserialize;
 
-use crate::noise_filter::NoiseFilter;
+use crate::{
+    erosion::{ErodedTerrain, ErosionSettings},
+    noise_filter::{NoiseFilter, NoiseSettings},
+    planet_seed::{PlanetSeed, SeedStream},
+    tectonics::TectonicSettings,
+};
 
-#[derive(InspectorOptions, Reflect, Resource)]
+/// Angular offset used to estimate the surface normal from neighbouring points.
+const NORMAL_SAMPLE_OFFSET: f32 = 1e-3;
+
+/// Number of layer values kept on the stack while the layers are blended. A mask that
+/// refers to a layer past these evaluates it again.
+const CACHED_LAYERS: usize = 16;
+
+#[derive(Clone, Deserialize, InspectorOptions, Reflect, Resource)]
 #[reflect(InspectorOptions, Resource)]
-pub(crate) struct PlanetSettings {
+#[serde(default)]
+pub struct PlanetSettings {
     #[inspector(min = 2, max = 255)]
     pub(crate) resolution: u32,
     pub(crate) color: Color,
     pub(crate) radius: f32,
+    /// Elevation of the ocean surface as a fraction of [`PlanetSettings::radius`].
+    pub(crate) sea_level: f32,
+    pub(crate) ocean_color: Color,
     pub(crate) noise_filters: Vec<NoiseFilter>,
+    /// Number of simulation cells along one edge of a face of the [`PlanetGrid`](crate::PlanetGrid).
+    #[inspector(min = 1, max = 128)]
+    pub(crate) grid_resolution: u32,
+    pub(crate) erosion: ErosionSettings,
+    #[reflect(ignore)]
+    #[serde(skip)]
+    pub(crate) eroded: ErodedTerrain,
 }
 
 impl PlanetSettings {
     pub(crate) fn calculate_point_on_planet(&self, point_on_unit_sphere: Vec3) -> Vec3 {
-        let mut elevation = 0.0;
-        let mut first_layer_value = 0.0;
-        if let Some(filter) = self.noise_filters.first() {
-            first_layer_value = filter.evaluate(point_on_unit_sphere);
-            elevation = first_layer_value;
+        point_on_unit_sphere * self.radius * (1.0 + self.calculate_elevation(point_on_unit_sphere))
+    }
+
+    /// Returns the elevation at a point as a fraction of [`PlanetSettings::radius`].
+    pub(crate) fn calculate_elevation(&self, point_on_unit_sphere: Vec3) -> f32 {
+        let elevation = self.calculate_noise_elevation(point_on_unit_sphere);
+        if !self.erosion.enabled {
+            return elevation;
         }
-        for filter in self.noise_filters.iter().skip(1) {
-            let mask = if filter.settings.use_first_layer_as_mask {
-                first_layer_value
-            } else {
-                1.0
-            };
-            elevation += filter.evaluate(point_on_unit_sphere) * mask;
+        elevation + self.eroded.change(point_on_unit_sphere)
+    }
+
+    /// Returns the elevation of the noise layers at a point, before erosion.
+    ///
+    /// The layers are blended in order. Every layer is evaluated first, because a layer can
+    /// be masked by any other one. This runs several times for every vertex, so the values
+    /// are kept on the stack instead of being collected.
+    pub(crate) fn calculate_noise_elevation(&self, point_on_unit_sphere: Vec3) -> f32 {
+        let mut values = [0.0; CACHED_LAYERS];
+        for (value, filter) in values.iter_mut().zip(&self.noise_filters) {
+            *value = filter.evaluate(point_on_unit_sphere);
+        }
+        let value_of = |layer: usize| {
+            let filter = self.noise_filters.get(layer)?;
+            Some(match values.get(layer) {
+                Some(&value) => value,
+                None => filter.evaluate(point_on_unit_sphere),
+            })
+        };
+        self.noise_filters
+            .iter()
+            .enumerate()
+            .fold(0.0, |elevation, (layer, filter)| {
+                let value = value_of(layer).unwrap_or_default();
+                let mask = filter
+                    .mask
+                    .as_ref()
+                    .map_or(1.0, |mask| mask.evaluate(value_of(mask.layer)));
+                filter.blend.apply(elevation, value, mask)
+            })
+    }
+
+    /// Returns the normal of the terrain at a point, pointing away from the planet.
+    ///
+    /// The normal is estimated from the slope of the elevation around the point, so it only
+    /// depends on the direction. Vertices that lie on the edge between two terrain faces
+    /// therefore get the same normal on both faces.
+    pub(crate) fn calculate_normal(&self, point_on_unit_sphere: Vec3) -> Vec3 {
+        let (tangent, bitangent) = point_on_unit_sphere.any_orthonormal_pair();
+        let point = |offset: Vec3| {
+            self.calculate_point_on_planet((point_on_unit_sphere + offset).normalize())
+        };
+        let along_tangent =
+            point(tangent * NORMAL_SAMPLE_OFFSET) - point(-tangent * NORMAL_SAMPLE_OFFSET);
+        let along_bitangent =
+            point(bitangent * NORMAL_SAMPLE_OFFSET) - point(-bitangent * NORMAL_SAMPLE_OFFSET);
+        let normal = along_tangent
+            .cross(along_bitangent)
+            .normalize_or(point_on_unit_sphere);
+        if normal.dot(point_on_unit_sphere) < 0.0 {
+            -normal
+        } else {
+            normal
         }
-        point_on_unit_sphere * self.radius * (1.0 + elevation)
     }
 
+    /// Returns whether an elevation from [`PlanetSettings::calculate_elevation`] lies above the ocean.
+    pub(crate) fn is_land(&self, elevation: f32) -> bool {
+        elevation > self.sea_level
+    }
+
+    /// Returns the first tectonic layer of the terrain and its settings.
+    pub(crate) fn tectonics(&self) -> Option<(&NoiseFilter, &TectonicSettings)> {
+        self.noise_filters
+            .iter()
+            .find_map(|filter| match &filter.settings {
+                NoiseSettings::Tectonic(settings) => Some((filter, settings)),
+                _ => None,
+            })
+    }
+
+    /// Gives every noise layer and the erosion their own seeds derived from the planet seed.
+    /// Returns whether anything was reseeded.
+    pub(crate) fn reseed(&mut self, seed: PlanetSeed) -> bool {
+        let mut reseeded = false;
+        for (layer, filter) in (0..).zip(&mut self.noise_filters) {
+            reseeded |= filter.reseed(seed.derive(SeedStream::Terrain, layer));
+        }
+        let erosion_seed = seed.derive(SeedStream::Erosion, 0);
+        if self.erosion.seed != erosion_seed {
+            self.erosion.seed = erosion_seed;
+            reseeded = true;
+        }
+        reseeded
+    }
+
+    #[cfg(test)]
     pub(crate) fn with_layer(mut self, layer: NoiseFilter) -> Self {
         self.noise_filters.push(layer);
         self
@@ -45,7 +149,182 @@ impl Default for PlanetSettings {
             resolution: 100,
             color: BLUE.into(),
             radius: 1.0,
+            sea_level: 0.0,
+            ocean_color: BLUE.into(),
             noise_filters: Vec::new(),
+            grid_resolution: 32,
+            erosion: ErosionSettings::default(),
+            eroded: ErodedTerrain::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        noise_filter::{BlendMode, FractalSettings, LayerMask, MaskCurve},
+        planet_preset::PlanetPreset,
+    };
+
+    /// The presets as they were written with `use_first_layer_as_mask`, and which of their
+    /// layers had it set.
+    const LEGACY_PRESETS: [(&str, &[bool]); 4] = [
+        (
+            include_str!("../../../assets/planets/earthlike.planet.ron"),
+            &[false, true],
+        ),
+        (
+            include_str!("../../../assets/planets/desert.planet.ron"),
+            &[false, true, false],
+        ),
+        (
+            include_str!("../../../assets/planets/ocean.planet.ron"),
+            &[false, true],
+        ),
+        (
+            include_str!("../../../assets/planets/ice.planet.ron"),
+            &[false, true],
+        ),
+    ];
+
+    /// How the elevation was calculated before layers had masks and blend modes.
+    fn legacy_elevation(settings: &PlanetSettings, masked: &[bool], point: Vec3) -> f32 {
+        let mut elevation = 0.0;
+        let mut first_layer_value = 0.0;
+        if let Some(filter) = settings.noise_filters.first() {
+            first_layer_value = filter.evaluate(point);
+            elevation = first_layer_value;
         }
+        for (filter, &masked) in settings.noise_filters.iter().zip(masked).skip(1) {
+            let mask = if masked { first_layer_value } else { 1.0 };
+            elevation += filter.evaluate(point) * mask;
+        }
+        elevation
+    }
+
+    /// Points spread evenly over the sphere.
+    fn points() -> impl Iterator<Item = Vec3> {
+        const COUNT: u32 = 2000;
+        (0..COUNT).map(|index| {
+            let y = 1.0 - 2.0 * (index as f32 + 0.5) / COUNT as f32;
+            let angle = index as f32 * std::f32::consts::PI * (3.0 - 5.0_f32.sqrt());
+            let (sin, cos) = angle.sin_cos();
+            Vec3::new(cos, 0.0, sin) * (1.0 - y * y).sqrt() + Vec3::Y * y
+        })
+    }
+
+    #[test]
+    fn presets_keep_their_legacy_elevation() {
+        for (source, masked) in LEGACY_PRESETS {
+            let preset: PlanetPreset = ron::de::from_str(source).unwrap();
+            let mut settings = preset.planet;
+            settings.reseed(PlanetSeed(3));
+            assert_eq!(settings.noise_filters.len(), masked.len());
+            for point in points() {
+                assert_eq!(
+                    settings.calculate_elevation(point).to_bits(),
+                    legacy_elevation(&settings, masked, point).to_bits(),
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn masks_can_refer_to_later_layers() {
+        let layer = |strength| {
+            NoiseFilter::new(NoiseSettings::Simple(FractalSettings {
+                number_of_layers: 3,
+                strength,
+                base_roughness: 1.0,
+                roughness: 2.0,
+                persistence: 0.5,
+                center: Vec3::ZERO,
+                min_value: 0.5,
+            }))
+        };
+        let mut masked = layer(1.0);
+        masked.mask = Some(LayerMask {
+            layer: 1,
+            curve: MaskCurve::Linear,
+        });
+        let mut mask = layer(0.5);
+        mask.blend = BlendMode::Max;
+        let mut settings = PlanetSettings::default()
+            .with_layer(masked)
+            .with_layer(mask);
+        settings.reseed(PlanetSeed(11));
+
+        for point in points() {
+            let [masked, mask] = [0, 1].map(|index| settings.noise_filters[index].evaluate(point));
+            let expected = (masked * mask).max(mask);
+            assert!((settings.calculate_elevation(point) - expected).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn masks_can_refer_to_layers_past_the_cached_ones() {
+        let layer = || {
+            NoiseFilter::new(NoiseSettings::Simple(FractalSettings {
+                number_of_layers: 2,
+                strength: 0.1,
+                base_roughness: 1.0,
+                roughness: 2.0,
+                persistence: 0.5,
+                center: Vec3::ZERO,
+                min_value: 0.3,
+            }))
+        };
+        let mut settings = PlanetSettings::default();
+        for _ in 0..CACHED_LAYERS + 2 {
+            settings = settings.with_layer(layer());
+        }
+        settings.noise_filters[0].mask = Some(LayerMask {
+            layer: CACHED_LAYERS + 1,
+            curve: MaskCurve::Linear,
+        });
+        settings.reseed(PlanetSeed(13));
+
+        for point in points() {
+            let values: Vec<f32> = settings
+                .noise_filters
+                .iter()
+                .map(|filter| filter.evaluate(point))
+                .collect();
+            let expected = values[0] * values[CACHED_LAYERS + 1] + values[1..].iter().sum::<f32>();
+            assert!((settings.calculate_elevation(point) - expected).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn blend_modes_combine_layers() {
+        assert_eq!(BlendMode::Add.apply(2.0, 3.0, 1.0), 5.0);
+        assert_eq!(BlendMode::Subtract.apply(2.0, 3.0, 1.0), -1.0);
+        assert_eq!(BlendMode::Multiply.apply(2.0, 3.0, 1.0), 6.0);
+        assert_eq!(BlendMode::Max.apply(2.0, 3.0, 1.0), 3.0);
+        for mode in [
+            BlendMode::Add,
+            BlendMode::Subtract,
+            BlendMode::Multiply,
+            BlendMode::Max,
+        ] {
+            assert_eq!(mode.apply(2.0, 3.0, 0.0), 2.0);
+        }
+    }
+
+    #[test]
+    fn smoothstep_masks_can_be_inverted() {
+        let rising = MaskCurve::Smoothstep { from: 0.0, to: 1.0 };
+        let falling = MaskCurve::Smoothstep { from: 1.0, to: 0.0 };
+        assert_eq!(rising.apply(-1.0), 0.0);
+        assert_eq!(rising.apply(0.5), 0.5);
+        assert_eq!(rising.apply(2.0), 1.0);
+        assert_eq!(falling.apply(-1.0), 1.0);
+        assert_eq!(falling.apply(2.0), 0.0);
+
+        let step = MaskCurve::Smoothstep { from: 0.5, to: 0.5 };
+        assert_eq!(step.apply(0.0), 0.0);
+        assert_eq!(step.apply(0.5), 1.0);
+        assert_eq!(step.apply(1.0), 1.0);
     }
 }
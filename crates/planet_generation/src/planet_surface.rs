@@ -0,0 +1,227 @@
+//! Answers "what is the ground here?" for any point on the planet without going
+//! through the render meshes.
+
+use bevy::{
+    ecs::system::SystemParam,
+    picking::mesh_picking::ray_cast::{MeshRayCast, MeshRayCastSettings},
+    prelude::*,
+};
+
+use crate::{
+    planet_generation::Planet, planet_settings::PlanetSettings, terrain_face::TerrainFace,
+};
+
+/// What the planet surface looks like at a single point.
+#[derive(Clone, Copy, Debug, PartialEq, Reflect)]
+pub struct SurfaceSample {
+    /// Unit direction from the planet center towards the sampled point.
+    pub direction: Vec3,
+    /// Position on the surface, relative to the planet center.
+    pub position: Vec3,
+    /// Height above the base radius of the planet, in world units.
+    pub elevation: f32,
+    /// Surface normal, pointing away from the planet.
+    pub normal: Vec3,
+    /// Whether the point lies above sea level.
+    pub is_land: bool,
+    /// The terrain face the point lies on.
+    pub face: FaceCoordinate,
+}
+
+/// A point on one of the six terrain faces.
+#[derive(Clone, Copy, Debug, PartialEq, Reflect)]
+pub struct FaceCoordinate {
+    /// The `local_up` of the terrain face.
+    pub local_up: Vec3,
+    /// The coordinate on that face, matching the mesh UVs.
+    pub uv: Vec2,
+}
+
+/// A [`SystemParam`] for sampling the planet surface.
+///
+/// ```ignore
+/// fn place_plant(surface: PlanetSurface) {
+///     let ground = surface.sample_lat_lon(12.0, -40.0);
+///     if ground.is_land {
+///         // ...
+///     }
+/// }
+/// ```
+#[derive(SystemParam)]
+pub struct PlanetSurface<'w> {
+    settings: Res<'w, PlanetSettings>,
+}
+
+impl PlanetSurface<'_> {
+    /// Samples the surface in the given direction from the planet center.
+    /// The direction does not have to be normalized.
+    pub fn sample(&self, direction: Vec3) -> SurfaceSample {
+        sample_surface(&self.settings, direction)
+    }
+
+    /// Samples the surface at the given latitude and longitude in degrees.
+    pub fn sample_lat_lon(&self, latitude: f32, longitude: f32) -> SurfaceSample {
+        self.sample(direction_from_lat_lon(latitude, longitude))
+    }
+
+    /// The base radius of the planet, before any noise is applied.
+    pub fn radius(&self) -> f32 {
+        self.settings.radius
+    }
+
+    /// The distance of the ocean surface from the planet center.
+    pub fn sea_level_radius(&self) -> f32 {
+        self.settings.radius * (1.0 + self.settings.sea_level)
+    }
+}
+
+/// A [`SystemParam`] for finding where a ray, like one through the cursor, hits the terrain
+/// meshes of the [`Planet`].
+#[derive(SystemParam)]
+pub struct TerrainRayCast<'w, 's> {
+    ray_cast: MeshRayCast<'w, 's>,
+    faces: Query<'w, 's, &'static ChildOf, With<TerrainFace>>,
+    planets: Query<'w, 's, &'static GlobalTransform, With<Planet>>,
+}
+
+impl TerrainRayCast<'_, '_> {
+    /// The first entity the ray hits and where, relative to the planet center.
+    ///
+    /// The ray passes through everything but the terrain and the entities `also_hit` accepts.
+    pub fn cast_ray(
+        &mut self,
+        ray: Ray3d,
+        also_hit: impl Fn(Entity) -> bool,
+    ) -> Option<(Entity, Vec3)> {
+        let planet = self.planets.single().ok()?;
+        let filter = |entity| {
+            also_hit(entity)
+                || self
+                    .faces
+                    .get(entity)
+                    .is_ok_and(|child_of| self.planets.contains(child_of.parent()))
+        };
+        let settings = MeshRayCastSettings::default()
+            .with_filter(&filter)
+            .always_early_exit();
+        let (entity, hit) = self.ray_cast.cast_ray(ray, &settings).first()?;
+        Some((
+            *entity,
+            planet.affine().inverse().transform_point3(hit.point),
+        ))
+    }
+}
+
+pub(crate) fn sample_surface(settings: &PlanetSettings, direction: Vec3) -> SurfaceSample {
+    let direction = direction.normalize();
+    let elevation = settings.calculate_elevation(direction);
+    let face = TerrainFace::containing(direction);
+    SurfaceSample {
+        direction,
+        position: direction * settings.radius * (1.0 + elevation),
+        elevation: elevation * settings.radius,
+        normal: settings.calculate_normal(direction),
+        is_land: settings.is_land(elevation),
+        face: FaceCoordinate {
+            local_up: face.local_up(),
+            uv: face.uv_of(direction),
+        },
+    }
+}
+
+/// Converts a latitude and longitude in degrees into a unit direction.
+///
+/// This uses the same convention as the orbit camera: latitude is measured
+/// towards `+Y` and longitude rotates from `+X` towards `+Z`.
+pub fn direction_from_lat_lon(latitude: f32, longitude: f32) -> Vec3 {
+    let (latitude, longitude) = (latitude.to_radians(), longitude.to_radians());
+    Vec3::new(
+        latitude.cos() * longitude.cos(),
+        latitude.sin(),
+        latitude.cos() * longitude.sin(),
+    )
+}
+
+/// Converts a direction into latitude and longitude in degrees.
+pub fn lat_lon_from_direction(direction: Vec3) -> (f32, f32) {
+    let direction = direction.normalize();
+    let latitude = direction.y.clamp(-1.0, 1.0).asin();
+    let longitude = direction.z.atan2(direction.x);
+    (latitude.to_degrees(), longitude.to_degrees())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 1e-3;
+
+    fn assert_round_trips(latitude: f32, longitude: f32) {
+        let direction = direction_from_lat_lon(latitude, longitude);
+        assert!((direction.length() - 1.0).abs() < EPSILON);
+        let (round_latitude, round_longitude) = lat_lon_from_direction(direction);
+        assert!(
+            (round_latitude - latitude).abs() < EPSILON,
+            "latitude {latitude} came back as {round_latitude}"
+        );
+        assert!(
+            direction_from_lat_lon(round_latitude, round_longitude).distance(direction) < EPSILON,
+            "({latitude}, {longitude}) came back as ({round_latitude}, {round_longitude})"
+        );
+    }
+
+    #[test]
+    fn lat_lon_round_trips_through_direction() {
+        for latitude in (-80..=80).step_by(20) {
+            for longitude in (-170..=170).step_by(34) {
+                let (latitude, longitude) = (latitude as f32, longitude as f32);
+                assert_round_trips(latitude, longitude);
+                let (_, round_longitude) =
+                    lat_lon_from_direction(direction_from_lat_lon(latitude, longitude));
+                assert!((round_longitude - longitude).abs() < EPSILON);
+            }
+        }
+    }
+
+    #[test]
+    fn poles_round_trip_to_the_axis() {
+        for longitude in [-180.0, -90.0, 0.0, 45.0, 180.0] {
+            assert_round_trips(90.0, longitude);
+            assert_round_trips(-90.0, longitude);
+        }
+        assert!(direction_from_lat_lon(90.0, 0.0).distance(Vec3::Y) < EPSILON);
+        assert!(direction_from_lat_lon(-90.0, 0.0).distance(Vec3::NEG_Y) < EPSILON);
+        assert!((lat_lon_from_direction(Vec3::Y).0 - 90.0).abs() < EPSILON);
+        assert!((lat_lon_from_direction(Vec3::NEG_Y).0 + 90.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn longitude_wraps_at_the_seam() {
+        for longitude in [-180.0, 180.0, 179.99, -179.99, 540.0] {
+            assert_round_trips(30.0, longitude);
+            let (_, round_longitude) =
+                lat_lon_from_direction(direction_from_lat_lon(30.0, longitude));
+            assert!((-180.0..=180.0).contains(&round_longitude));
+        }
+        assert!(
+            direction_from_lat_lon(10.0, 180.0).distance(direction_from_lat_lon(10.0, -180.0))
+                < EPSILON
+        );
+    }
+
+    #[test]
+    fn samples_match_the_requested_direction() {
+        let settings = PlanetSettings {
+            radius: 2.0,
+            ..default()
+        };
+        for (latitude, longitude) in [(0.0, 0.0), (45.0, 180.0), (-90.0, 0.0), (90.0, -180.0)] {
+            let sample = sample_surface(&settings, direction_from_lat_lon(latitude, longitude));
+            assert!((sample.direction.length() - 1.0).abs() < EPSILON);
+            assert!(sample.position.distance(sample.direction * 2.0) < EPSILON);
+            assert!(sample.normal.dot(sample.direction) > 0.99);
+        }
+        let sample = sample_surface(&settings, Vec3::new(0.0, 0.0, 5.0));
+        assert_eq!(sample.direction, Vec3::Z);
+    }
+}
@@ -0,0 +1,414 @@
+//! Plate tectonics. The surface is split into plates, the cells of a Voronoi diagram on the
+//! sphere, and every plate rotates about its own pole. Where plates collide mountains rise,
+//! where they drift apart the crust rifts, and both kinds of boundary heat the ground, as do
+//! hot spots in the mantle.
+//!
+//! The plates are derived from a seed instead of being stored in presets. They are built
+//! the first time the terrain is sampled and shared from then on, so tectonics can be used
+//! as a layer of the terrain like any noise and be evaluated on any thread.
+
+use std::sync::{Arc, OnceLock};
+
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::{
+    geothermal_field::GeothermalField,
+    noise_filter::{NoiseFilter, NoiseSettings},
+    planet_seed::{SeededRandom, apply_planet_seed},
+    planet_settings::PlanetSettings,
+};
+
+/// Most plates that can be close enough to a point to shape it. Far more than ever meet.
+const MAX_NEARBY_PLATES: usize = 8;
+
+pub(crate) fn plugin(app: &mut App) {
+    app.add_systems(
+        Update,
+        (discard_plates, feed_geothermal_field)
+            .chain()
+            .after(apply_planet_seed)
+            .run_if(resource_changed::<PlanetSettings>),
+    );
+}
+
+#[derive(Clone, Deserialize, Reflect)]
+pub(crate) struct TectonicSettings {
+    pub(crate) plate_count: u32,
+    /// Share of the plates that carry a continent.
+    pub(crate) continental_fraction: f32,
+    /// Elevation of continental plates. Oceanic plates lie at zero.
+    pub(crate) continent_height: f32,
+    /// Height of the mountains where two plates collide head-on at full speed.
+    pub(crate) mountain_height: f32,
+    /// Depth of the rift where two plates drift apart at full speed.
+    pub(crate) rift_depth: f32,
+    /// How far from a boundary, in radians, it shapes the terrain.
+    pub(crate) boundary_width: f32,
+    /// Heat at a boundary where two plates move at full speed relative to each other.
+    pub(crate) boundary_heat: f32,
+    pub(crate) hotspot_count: u32,
+    /// Angular radius of a hot spot, in radians.
+    pub(crate) hotspot_radius: f32,
+    pub(crate) hotspot_heat: f32,
+    #[reflect(ignore)]
+    #[serde(skip)]
+    pub(crate) plates: PlateCache,
+}
+
+/// The plates of a seed, shared by every clone of the [`TectonicSettings`], like the
+/// [`ErodedTerrain`](crate::erosion::ErodedTerrain).
+#[derive(Clone, Default)]
+pub(crate) struct PlateCache(Arc<OnceLock<Plates>>);
+
+/// One tectonic plate.
+struct Plate {
+    /// The center of the plate's Voronoi cell. Every point belongs to the closest center.
+    center: Vec3,
+    /// The axis the plate rotates about, scaled by its angular speed.
+    rotation: Vec3,
+    continental: bool,
+}
+
+impl Plate {
+    fn velocity(&self, point: Vec3) -> Vec3 {
+        self.rotation.cross(point)
+    }
+}
+
+/// The plates and hot spots drawn from one seed.
+struct Plates {
+    seed: u32,
+    plates: Vec<Plate>,
+    hotspots: Vec<Vec3>,
+}
+
+impl Plates {
+    fn new(settings: &TectonicSettings, seed: u32) -> Self {
+        let mut random = SeededRandom::new(u64::from(seed));
+        let plates = (0..settings.plate_count)
+            .map(|_| Plate {
+                center: random.next_direction(),
+                rotation: random.next_direction() * random.next_f32(),
+                continental: random.next_f32() < settings.continental_fraction,
+            })
+            .collect();
+        // Hot spots are drawn from their own sequence, so they don't move when the number
+        // of plates changes.
+        let mut random = SeededRandom::new(!u64::from(seed));
+        let hotspots = (0..settings.hotspot_count)
+            .map(|_| random.next_direction())
+            .collect();
+        Self {
+            seed,
+            plates,
+            hotspots,
+        }
+    }
+
+    /// Returns the plates whose boundaries are close enough to affect a point, weighted from
+    /// `1.0` for the plate the point lies on down to `0.0` at [`TectonicSettings::boundary_width`].
+    ///
+    /// A plate is as close as its center compared to the nearest center. On a boundary both
+    /// plates are equally close, so the weights and everything blended with them are
+    /// continuous across it.
+    fn nearby(&self, settings: &TectonicSettings, point_on_unit_sphere: Vec3) -> NearbyPlates<'_> {
+        let angle = |plate: &Plate| plate.center.angle_between(point_on_unit_sphere);
+        let nearest = self.plates.iter().map(angle).fold(f32::INFINITY, f32::min);
+        let width = settings.boundary_width.max(f32::EPSILON);
+        let mut nearby = NearbyPlates::default();
+        for plate in &self.plates {
+            let weight = 1.0 - smoothstep((angle(plate) - nearest) / width);
+            if weight > 0.0 {
+                nearby.push(plate, weight);
+            }
+        }
+        nearby
+    }
+
+    fn nearest(&self, point_on_unit_sphere: Vec3) -> Option<usize> {
+        self.plates
+            .iter()
+            .map(|plate| plate.center.dot(point_on_unit_sphere))
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(index, _)| index)
+    }
+}
+
+/// The plates near a point with their weights, kept on the stack because they are looked
+/// up for every vertex.
+#[derive(Default)]
+struct NearbyPlates<'a> {
+    plates: [Option<(&'a Plate, f32)>; MAX_NEARBY_PLATES],
+    len: usize,
+}
+
+impl<'a> NearbyPlates<'a> {
+    fn push(&mut self, plate: &'a Plate, weight: f32) {
+        if let Some(slot) = self.plates.get_mut(self.len) {
+            *slot = Some((plate, weight));
+            self.len += 1;
+        }
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (&'a Plate, f32)> + '_ {
+        self.plates[..self.len].iter().flatten().copied()
+    }
+
+    /// Calls `boundary` for every pair of nearby plates, with the weight of their boundary.
+    fn for_each_boundary(&self, mut boundary: impl FnMut(&Plate, &Plate, f32)) {
+        for (index, (a, a_weight)) in self.iter().enumerate() {
+            for (b, b_weight) in self.iter().skip(index + 1) {
+                boundary(a, b, a_weight * b_weight);
+            }
+        }
+    }
+}
+
+impl TectonicSettings {
+    /// Returns the elevation of the plates at a point, as a fraction of the planet radius.
+    pub(crate) fn elevation(&self, seed: u32, point_on_unit_sphere: Vec3) -> f32 {
+        self.with_plates(seed, |plates| {
+            self.plates_elevation(plates, point_on_unit_sphere)
+        })
+    }
+
+    /// Returns the heat rising from boundaries and hot spots at a point.
+    pub(crate) fn heat(&self, seed: u32, point_on_unit_sphere: Vec3) -> f32 {
+        self.with_plates(seed, |plates| {
+            self.plates_heat(plates, point_on_unit_sphere)
+        })
+    }
+
+    /// Returns the index of the plate a point belongs to.
+    pub(crate) fn plate_at(&self, seed: u32, point_on_unit_sphere: Vec3) -> u32 {
+        self.with_plates(seed, |plates| {
+            plates
+                .nearest(point_on_unit_sphere)
+                .map_or(0, |plate| plate as u32)
+        })
+    }
+
+    /// Runs `f` with the plates of a seed. They are built once and cached. A seed other
+    /// than the cached one gets plates of its own, until the cache is discarded.
+    fn with_plates<T>(&self, seed: u32, f: impl FnOnce(&Plates) -> T) -> T {
+        let plates = self.plates.0.get_or_init(|| Plates::new(self, seed));
+        if plates.seed == seed {
+            f(plates)
+        } else {
+            f(&Plates::new(self, seed))
+        }
+    }
+
+    fn plates_elevation(&self, plates: &Plates, point_on_unit_sphere: Vec3) -> f32 {
+        let nearby = plates.nearby(self, point_on_unit_sphere);
+        // Blend the plate heights across boundaries, so continents don't end in cliffs.
+        let total_weight: f32 = nearby.iter().map(|(_, weight)| weight).sum();
+        let mut elevation = nearby
+            .iter()
+            .map(|(plate, weight)| self.plate_height(plate) * weight)
+            .sum::<f32>()
+            / total_weight.max(f32::EPSILON);
+        nearby.for_each_boundary(|a, b, weight| {
+            let convergence = convergence(a, b, point_on_unit_sphere);
+            let height = if convergence > 0.0 {
+                self.mountain_height
+            } else {
+                self.rift_depth
+            };
+            elevation += convergence * height * weight;
+        });
+        elevation
+    }
+
+    fn plates_heat(&self, plates: &Plates, point_on_unit_sphere: Vec3) -> f32 {
+        let mut heat = 0.0;
+        plates
+            .nearby(self, point_on_unit_sphere)
+            .for_each_boundary(|a, b, weight| {
+                heat += convergence(a, b, point_on_unit_sphere).abs() * self.boundary_heat * weight;
+            });
+        let radius = self.hotspot_radius.max(f32::EPSILON);
+        for hotspot in &plates.hotspots {
+            let distance = point_on_unit_sphere.angle_between(*hotspot);
+            heat += (1.0 - smoothstep(distance / radius)) * self.hotspot_heat;
+        }
+        heat
+    }
+
+    fn plate_height(&self, plate: &Plate) -> f32 {
+        if plate.continental {
+            self.continent_height
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Returns how fast two plates move towards each other at a point. Negative if they move
+/// apart.
+fn convergence(a: &Plate, b: &Plate, point_on_unit_sphere: Vec3) -> f32 {
+    let normal = (b.center - a.center).normalize_or_zero();
+    (a.velocity(point_on_unit_sphere) - b.velocity(point_on_unit_sphere)).dot(normal)
+}
+
+/// Eases from `0.0` at `x = 0` and below to `1.0` at `x = 1` and above.
+fn smoothstep(x: f32) -> f32 {
+    let x = x.clamp(0.0, 1.0);
+    x * x * (3.0 - 2.0 * x)
+}
+
+/// The plates are built from the settings, so they have to be built again whenever the
+/// settings change.
+fn discard_plates(mut settings: ResMut<PlanetSettings>) {
+    for filter in &mut settings.bypass_change_detection().noise_filters {
+        if let NoiseSettings::Tectonic(tectonics) = &mut filter.settings {
+            tectonics.plates = PlateCache::default();
+        }
+    }
+}
+
+/// Lets the plates of the terrain heat the [`GeothermalField`].
+fn feed_geothermal_field(
+    settings: Res<PlanetSettings>,
+    mut geothermal_field: ResMut<GeothermalField>,
+) {
+    geothermal_field.tectonic_heat = settings.tectonics().map(|(layer, tectonics)| NoiseFilter {
+        noise: layer.noise,
+        ..NoiseFilter::new(NoiseSettings::TectonicHeat(tectonics.clone()))
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+    use noise::Seedable;
+
+    use super::*;
+
+    fn settings() -> TectonicSettings {
+        TectonicSettings {
+            plate_count: 12,
+            continental_fraction: 0.4,
+            continent_height: 0.1,
+            mountain_height: 0.2,
+            rift_depth: 0.1,
+            boundary_width: 0.2,
+            boundary_heat: 1.0,
+            hotspot_count: 0,
+            hotspot_radius: 0.1,
+            hotspot_heat: 1.0,
+            plates: PlateCache::default(),
+        }
+    }
+
+    /// An oceanic plate centered on `+X` that rotates about `Y`, next to a resting one
+    /// centered on `+Z`. Their boundary runs through the point between the two centers.
+    fn two_plates(rotation: f32) -> Plates {
+        let plate = |center, rotation| Plate {
+            center,
+            rotation,
+            continental: false,
+        };
+        Plates {
+            seed: 0,
+            plates: vec![
+                plate(Vec3::X, Vec3::Y * rotation),
+                plate(Vec3::Z, Vec3::ZERO),
+            ],
+            hotspots: Vec::new(),
+        }
+    }
+
+    /// Points spread evenly over the sphere.
+    fn points() -> impl Iterator<Item = Vec3> {
+        const COUNT: u32 = 500;
+        (0..COUNT).map(|index| {
+            let y = 1.0 - 2.0 * (index as f32 + 0.5) / COUNT as f32;
+            let angle = index as f32 * std::f32::consts::PI * (3.0 - 5.0_f32.sqrt());
+            let (sin, cos) = angle.sin_cos();
+            Vec3::new(cos, 0.0, sin) * (1.0 - y * y).sqrt() + Vec3::Y * y
+        })
+    }
+
+    #[test]
+    fn same_seed_yields_the_same_plates() {
+        // Separate settings, so the second run can't reuse the plates of the first.
+        let (first, second) = (settings(), settings());
+        for point in points() {
+            assert_eq!(first.plate_at(7, point), second.plate_at(7, point));
+            assert_eq!(
+                first.elevation(7, point).to_bits(),
+                second.elevation(7, point).to_bits()
+            );
+        }
+        assert!(points().any(|point| first.plate_at(7, point) != first.plate_at(8, point)));
+    }
+
+    #[test]
+    fn cached_plates_belong_to_their_seed() {
+        let cached = settings();
+        for point in points() {
+            cached.elevation(1, point);
+        }
+        let fresh = settings();
+        for point in points() {
+            assert_eq!(
+                cached.elevation(2, point).to_bits(),
+                fresh.elevation(2, point).to_bits()
+            );
+        }
+    }
+
+    #[test]
+    fn colliding_plates_raise_mountains() {
+        let settings = settings();
+        let boundary = Vec3::new(1.0, 0.0, 1.0).normalize();
+        // Turning clockwise about `Y` moves the plate on `+X` towards `+Z`.
+        let colliding = two_plates(-1.0);
+        let interior = settings.plates_elevation(&colliding, Vec3::X);
+        assert!(
+            settings.plates_elevation(&colliding, boundary)
+                > interior + 0.5 * settings.mountain_height
+        );
+
+        let drifting = two_plates(1.0);
+        let interior = settings.plates_elevation(&drifting, Vec3::X);
+        assert!(
+            settings.plates_elevation(&drifting, boundary) < interior - 0.5 * settings.rift_depth
+        );
+    }
+
+    #[test]
+    fn boundaries_are_hotter_than_plate_interiors() {
+        let settings = settings();
+        let boundary = Vec3::new(1.0, 0.0, 1.0).normalize();
+        for plates in [two_plates(-1.0), two_plates(1.0)] {
+            assert_eq!(settings.plates_heat(&plates, Vec3::X), 0.0);
+            assert!(settings.plates_heat(&plates, boundary) > 0.5 * settings.boundary_heat);
+        }
+    }
+
+    #[test]
+    fn plates_add_their_heat_to_the_geothermal_field() {
+        let mut world = World::new();
+        world.insert_resource(
+            PlanetSettings::default()
+                .with_layer(NoiseFilter::new(NoiseSettings::Tectonic(settings()))),
+        );
+        world.init_resource::<GeothermalField>();
+        world.run_system_once(feed_geothermal_field).unwrap();
+
+        let field = world.resource::<GeothermalField>();
+        let noise_only = GeothermalField::default();
+        let seed = world.resource::<PlanetSettings>().noise_filters[0].noise.seed();
+        let mut hottest = 0.0_f32;
+        for point in points() {
+            let tectonic_heat = field.heat(point) - noise_only.heat(point);
+            assert!((tectonic_heat - settings().heat(seed, point)).abs() < 1e-5);
+            hottest = hottest.max(tectonic_heat);
+        }
+        assert!(hottest > 0.5 * settings().boundary_heat);
+    }
+}
@@ -0,0 +1,157 @@
+//! Level of detail for the terrain. Every [`TerrainFace`] is a quadtree of chunks, and
+//! chunks close to the camera are split into four smaller ones with the same number of
+//! vertices, while distant chunks are merged back together.
+//!
+//! Only the leaves of the quadtrees exist as entities. They are children of the [`Planet`].
+
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use common::{states::Screen, system_sets::AppSet};
+
+use crate::{
+    Planet,
+    mesh_generation::{MeshGeneration, finish_mesh_generation},
+    overlay_material::OverlayMaterial,
+    planet_settings::PlanetSettings,
+    terrain_face::{DIRECTIONS, TerrainFace},
+};
+
+/// How deep the quadtree of a face may get. Chunks at this depth span 1/64th of a face.
+const MAX_DEPTH: u32 = 6;
+/// A chunk is split once the camera is closer than this many times the chunk's size.
+const SPLIT_DISTANCE: f32 = 2.0;
+
+pub(crate) fn plugin(app: &mut App) {
+    app.register_type::<TerrainChunk>().add_systems(
+        Update,
+        update_level_of_detail
+            .after(finish_mesh_generation)
+            .in_set(AppSet::Update)
+            .run_if(in_state(Screen::Gameplay)),
+    );
+}
+
+/// A square section of a [`TerrainFace`]. The chunk at depth zero covers the whole face,
+/// and every level down halves the chunk's size.
+#[derive(Clone, Copy, Component, Debug, Default, Eq, Hash, PartialEq, Reflect)]
+#[reflect(Component)]
+pub struct TerrainChunk {
+    pub depth: u32,
+    /// Position of the chunk among the `2^depth * 2^depth` chunks at its depth.
+    pub x: u32,
+    pub y: u32,
+}
+
+impl TerrainChunk {
+    /// Length of the chunk's edges in face coordinates.
+    pub fn size(&self) -> f32 {
+        1.0 / (1u32 << self.depth) as f32
+    }
+
+    /// The face coordinates this chunk covers.
+    pub fn bounds(&self) -> Rect {
+        let min = UVec2::new(self.x, self.y).as_vec2() * self.size();
+        Rect::from_corners(min, min + self.size())
+    }
+
+    pub fn children(&self) -> [Self; 4] {
+        let (x, y) = (self.x * 2, self.y * 2);
+        [(0, 0), (1, 0), (0, 1), (1, 1)].map(|(dx, dy)| Self {
+            depth: self.depth + 1,
+            x: x + dx,
+            y: y + dy,
+        })
+    }
+}
+
+/// The material shared by all chunks of the planet's terrain.
+#[derive(Resource)]
+pub(crate) struct TerrainMaterial(pub Handle<OverlayMaterial>);
+
+/// Chunks are identified by their face and their place in its quadtree.
+type ChunkKey = (IVec3, TerrainChunk);
+
+/// Splits chunks near the camera and merges the ones that are far away.
+///
+/// New chunks are generated in the background. The chunks they replace stay around until
+/// every new chunk covering the same area has its mesh, so the terrain never has holes.
+fn update_level_of_detail(
+    settings: Res<PlanetSettings>,
+    material: Res<TerrainMaterial>,
+    camera: Single<&GlobalTransform, With<Camera3d>>,
+    planet: Single<(Entity, &GlobalTransform, &Children), With<Planet>>,
+    chunks: Query<(Entity, &TerrainFace, &TerrainChunk, Has<MeshGeneration>)>,
+    mut commands: Commands,
+) {
+    let (planet, planet_transform, children) = planet.into_inner();
+    // Working in the planet's space keeps this correct if the planet ever moves or rotates.
+    let camera_position = planet_transform
+        .affine()
+        .inverse()
+        .transform_point3(camera.translation());
+
+    let mut wanted = HashMap::new();
+    for local_up in DIRECTIONS {
+        let face = TerrainFace::new(local_up);
+        select_chunks(
+            face,
+            TerrainChunk::default(),
+            camera_position,
+            &settings,
+            &mut wanted,
+        );
+    }
+
+    let mut existing = HashMap::new();
+    let mut generating = Vec::new();
+    for (entity, face, chunk, is_generating) in chunks.iter_many(children) {
+        let key = (face.local_up().as_ivec3(), *chunk);
+        if is_generating && wanted.contains_key(&key) {
+            generating.push(key);
+        }
+        existing.insert(key, entity);
+    }
+
+    for (key, face) in &wanted {
+        if existing.contains_key(key) {
+            continue;
+        }
+        let chunk = key.1;
+        commands.entity(planet).with_child((
+            *face,
+            chunk,
+            MeshGeneration::start(*face, chunk, settings.clone()),
+            MeshMaterial3d(material.0.clone()),
+        ));
+        generating.push(*key);
+    }
+
+    let overlaps =
+        |a: &ChunkKey, b: &ChunkKey| a.0 == b.0 && !a.1.bounds().intersect(b.1.bounds()).is_empty();
+    for (key, entity) in existing {
+        if wanted.contains_key(&key) || generating.iter().any(|other| overlaps(&key, other)) {
+            continue;
+        }
+        commands.entity(entity).despawn();
+    }
+}
+
+fn select_chunks(
+    face: TerrainFace,
+    chunk: TerrainChunk,
+    camera_position: Vec3,
+    settings: &PlanetSettings,
+    wanted: &mut HashMap<ChunkKey, TerrainFace>,
+) {
+    let center = face.point_on_unit_sphere(chunk.bounds().center()) * settings.radius;
+    // A face spans two units of the unit cube, which is roughly its size on the sphere.
+    let size = 2.0 * chunk.size() * settings.radius;
+    if chunk.depth < MAX_DEPTH && camera_position.distance(center) < size * SPLIT_DISTANCE {
+        for child in chunk.children() {
+            select_chunks(face, child, camera_position, settings, wanted);
+        }
+    } else {
+        wanted.insert((face.local_up().as_ivec3(), chunk), face);
+    }
+}
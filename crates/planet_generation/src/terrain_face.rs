@@ -1,13 +1,34 @@
 use bevy::{
     asset::RenderAssetUsages,
     prelude::*,
-    render::mesh::{Indices, PrimitiveTopology},
+    render::{
+        mesh::{Indices, MeshVertexAttribute, PrimitiveTopology, VertexAttributeValues},
+        render_resource::VertexFormat,
+    },
 };
 
-use crate::planet_settings::PlanetSettings;
+use crate::{planet_settings::PlanetSettings, terrain_chunk::TerrainChunk};
 
-#[derive(Component, Reflect)]
-pub(crate) struct TerrainFace {
+/// The `local_up` directions of the six faces that make up the cube-sphere.
+pub(crate) const DIRECTIONS: [Vec3; 6] = [
+    Vec3::X,
+    Vec3::Y,
+    Vec3::Z,
+    Vec3::NEG_X,
+    Vec3::NEG_Y,
+    Vec3::NEG_Z,
+];
+
+/// Height of a vertex above sea level in world units.
+/// Vertices with a negative value lie on the seabed.
+pub const ATTRIBUTE_SEA_LEVEL_ELEVATION: MeshVertexAttribute = MeshVertexAttribute::new(
+    "Vertex_SeaLevelElevation",
+    988_540_917,
+    VertexFormat::Float32,
+);
+
+#[derive(Clone, Copy, Component, Reflect)]
+pub struct TerrainFace {
     local_up: Vec3,
     axis_a: Vec3,
     axis_b: Vec3,
@@ -24,23 +45,71 @@ impl TerrainFace {
         }
     }
 
-    pub(crate) fn to_mesh(&self, settings: &PlanetSettings) -> Mesh {
+    /// Returns the face that the given direction passes through.
+    pub(crate) fn containing(direction: Vec3) -> Self {
+        let local_up = DIRECTIONS
+            .into_iter()
+            .max_by(|a, b| a.dot(direction).total_cmp(&b.dot(direction)))
+            .unwrap();
+        Self::new(local_up)
+    }
+
+    pub(crate) fn local_up(&self) -> Vec3 {
+        self.local_up
+    }
+
+    /// Maps a coordinate on this face onto the unit sphere. This is the inverse of
+    /// [`TerrainFace::uv_of`].
+    pub(crate) fn point_on_unit_sphere(&self, uv: Vec2) -> Vec3 {
+        self.point_on_unit_cube(uv).normalize()
+    }
+
+    /// Maps a coordinate on this face onto the unit cube. Coordinates outside of `0..=1`
+    /// land on the plane of this face beyond its edges.
+    pub(crate) fn point_on_unit_cube(&self, uv: Vec2) -> Vec3 {
+        self.point_on_cube_face((uv - 0.5) * 2.0)
+    }
+
+    /// Maps a coordinate in `-1..=1` around the center of this face onto the unit cube.
+    pub(crate) fn point_on_cube_face(&self, coordinate: Vec2) -> Vec3 {
+        self.local_up + coordinate.x * self.axis_a + coordinate.y * self.axis_b
+    }
+
+    /// Projects a direction onto this face, returning the same coordinate
+    /// that [`TerrainFace::to_mesh`] writes into `ATTRIBUTE_UV_0`.
+    pub(crate) fn uv_of(&self, direction: Vec3) -> Vec2 {
+        let point_on_unit_cube = direction / direction.dot(self.local_up);
+        Vec2::new(
+            point_on_unit_cube.dot(self.axis_a),
+            point_on_unit_cube.dot(self.axis_b),
+        ) * 0.5
+            + 0.5
+    }
+
+    /// Builds the mesh for a chunk of this face. Every chunk has `settings.resolution`
+    /// vertices along each edge, no matter how much of the face it covers.
+    pub(crate) fn to_mesh(self, settings: &PlanetSettings, chunk: TerrainChunk) -> Mesh {
         let resolution = settings.resolution;
+        // Number of vertex spacings across the whole face at the chunk's level of detail.
+        let spacings = (resolution - 1) << chunk.depth;
+        let offset = UVec2::new(chunk.x, chunk.y) * (resolution - 1);
         let mut vertices: Vec<Vec3> = Vec::with_capacity((resolution * resolution) as usize);
         let mut indices: Vec<u32> =
             Vec::with_capacity((resolution - 1) as usize * (resolution - 1) as usize);
         let mut uvs: Vec<Vec2> = Vec::with_capacity((resolution * resolution) as usize);
+        let mut sea_level_elevations: Vec<f32> =
+            Vec::with_capacity((resolution * resolution) as usize);
+        let mut normals: Vec<Vec3> = Vec::with_capacity((resolution * resolution) as usize);
 
         for y in 0..resolution {
             for x in 0..resolution {
-                let percent = UVec2::new(x, y).as_vec2() / (resolution - 1) as f32;
-                let point_on_unit_cube = self.local_up
-                    + (percent.x - 0.5) * 2.0 * self.axis_a
-                    + (percent.y - 0.5) * 2.0 * self.axis_b;
-                let point_on_unit_sphere = point_on_unit_cube.normalize();
-                let point_on_planet = settings.calculate_point_on_planet(point_on_unit_sphere);
-                vertices.push(point_on_planet);
-                uvs.push(percent);
+                let coordinate = grid_coordinate(offset + UVec2::new(x, y), spacings);
+                let point_on_unit_sphere = self.point_on_cube_face(coordinate).normalize();
+                let elevation = settings.calculate_elevation(point_on_unit_sphere);
+                vertices.push(point_on_unit_sphere * settings.radius * (1.0 + elevation));
+                normals.push(settings.calculate_normal(point_on_unit_sphere));
+                uvs.push(coordinate * 0.5 + 0.5);
+                sea_level_elevations.push((elevation - settings.sea_level) * settings.radius);
 
                 if x != resolution - 1 && y != resolution - 1 {
                     let index = x + y * resolution;
@@ -55,13 +124,222 @@ impl TerrainFace {
             }
         }
 
-        Mesh::new(
+        let mut mesh = Mesh::new(
             PrimitiveTopology::TriangleList,
             RenderAssetUsages::MAIN_WORLD | RenderAssetUsages::RENDER_WORLD,
         )
         .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, vertices)
         .with_inserted_indices(Indices::U32(indices))
         .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, uvs)
-        .with_computed_normals()
+        .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, normals)
+        .with_inserted_attribute(ATTRIBUTE_SEA_LEVEL_ELEVATION, sea_level_elevations);
+        add_skirt(
+            &mut mesh,
+            resolution,
+            settings.radius * chunk.size() * SKIRT_DEPTH,
+        );
+        mesh
+    }
+}
+
+/// Returns the coordinate in `-1..=1` of a point on a grid with `spacings` cells along each
+/// edge of a face.
+///
+/// Coordinates are derived from whole numbers, so the same point on the edge of two
+/// adjacent faces comes out bit for bit the same on both of them.
+pub(crate) fn grid_coordinate(point: UVec2, spacings: u32) -> Vec2 {
+    (point.as_vec2() * 2.0 - spacings as f32) / spacings as f32
+}
+
+/// How far skirts hang below the edge of a chunk, relative to the planet radius and the
+/// size of the chunk. Coarser chunks are further off the true surface and need deeper skirts.
+const SKIRT_DEPTH: f32 = 0.05;
+
+/// Hangs a strip of triangles below every edge of a chunk mesh. Where chunks of different
+/// detail meet, their edges don't line up exactly, and the skirts hide the cracks in between.
+///
+/// Skirt vertices copy the normal of the edge vertex above them, so they are lit like the
+/// terrain they extend.
+fn add_skirt(mesh: &mut Mesh, resolution: u32, depth: f32) {
+    let last = resolution - 1;
+    // Walking the border counterclockwise makes one winding face away from the chunk on
+    // every edge.
+    let edges: [Vec<u32>; 4] = [
+        (0..resolution).collect(),
+        (0..resolution).map(|i| i * resolution + last).collect(),
+        (0..resolution)
+            .rev()
+            .map(|i| last * resolution + i)
+            .collect(),
+        (0..resolution).rev().map(|i| i * resolution).collect(),
+    ];
+
+    let first_skirt_vertex = mesh.count_vertices() as u32;
+    let mut copied: Vec<usize> = Vec::with_capacity(4 * resolution as usize);
+    let mut indices: Vec<u32> = Vec::with_capacity(24 * last as usize);
+    for edge in edges {
+        let base = first_skirt_vertex + copied.len() as u32;
+        for (i, pair) in edge.windows(2).enumerate() {
+            let (a, b) = (pair[0], pair[1]);
+            let (skirt_a, skirt_b) = (base + i as u32, base + i as u32 + 1);
+            indices.extend([a, skirt_a, b, b, skirt_a, skirt_b]);
+        }
+        copied.extend(edge.iter().map(|&i| i as usize));
+    }
+
+    if let Some(VertexAttributeValues::Float32x3(positions)) =
+        mesh.attribute_mut(Mesh::ATTRIBUTE_POSITION)
+    {
+        let lowered: Vec<[f32; 3]> = copied
+            .iter()
+            .map(|&i| {
+                let position = Vec3::from(positions[i]);
+                (position - position.normalize() * depth).to_array()
+            })
+            .collect();
+        positions.extend(lowered);
+    }
+    for attribute in [
+        Mesh::ATTRIBUTE_NORMAL,
+        Mesh::ATTRIBUTE_UV_0,
+        ATTRIBUTE_SEA_LEVEL_ELEVATION,
+    ] {
+        match mesh.attribute_mut(attribute) {
+            Some(VertexAttributeValues::Float32x3(values)) => {
+                let copies: Vec<_> = copied.iter().map(|&i| values[i]).collect();
+                values.extend(copies);
+            }
+            Some(VertexAttributeValues::Float32x2(values)) => {
+                let copies: Vec<_> = copied.iter().map(|&i| values[i]).collect();
+                values.extend(copies);
+            }
+            Some(VertexAttributeValues::Float32(values)) => {
+                let copies: Vec<_> = copied.iter().map(|&i| values[i]).collect();
+                values.extend(copies);
+            }
+            _ => {}
+        }
+    }
+    if let Some(Indices::U32(mesh_indices)) = mesh.indices_mut() {
+        mesh_indices.extend(indices);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::noise_filter::{FractalSettings, NoiseFilter, NoiseSettings};
+
+    const RESOLUTION: u32 = 9;
+
+    fn settings() -> PlanetSettings {
+        PlanetSettings {
+            resolution: RESOLUTION,
+            ..default()
+        }
+        .with_layer(NoiseFilter::new(NoiseSettings::Simple(FractalSettings {
+            number_of_layers: 4,
+            strength: 0.3,
+            base_roughness: 1.5,
+            roughness: 2.0,
+            persistence: 0.5,
+            center: Vec3::ZERO,
+            min_value: 0.8,
+        })))
+    }
+
+    /// A vertex on the border of a chunk: the face it belongs to, its normal and its
+    /// coordinate on the vertex grid of the whole face.
+    type BorderVertex = (Vec3, Vec3, UVec2);
+
+    /// Collects the vertices on the border of every chunk at the given depth, grouped by
+    /// their exact position.
+    fn border_vertices(depth: u32) -> HashMap<[u32; 3], Vec<BorderVertex>> {
+        let settings = settings();
+        let last = RESOLUTION - 1;
+        let mut vertices: HashMap<[u32; 3], Vec<BorderVertex>> = HashMap::new();
+        for local_up in DIRECTIONS {
+            let face = TerrainFace::new(local_up);
+            for y in 0..1 << depth {
+                for x in 0..1 << depth {
+                    let mesh = face.to_mesh(&settings, TerrainChunk { depth, x, y });
+                    let Some(VertexAttributeValues::Float32x3(positions)) =
+                        mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+                    else {
+                        panic!("terrain mesh has no positions");
+                    };
+                    let Some(VertexAttributeValues::Float32x3(normals)) =
+                        mesh.attribute(Mesh::ATTRIBUTE_NORMAL)
+                    else {
+                        panic!("terrain mesh has no normals");
+                    };
+                    // The skirt comes after the grid, so the first vertices are the surface.
+                    for (index, (position, normal)) in positions
+                        .iter()
+                        .zip(normals)
+                        .take((RESOLUTION * RESOLUTION) as usize)
+                        .enumerate()
+                    {
+                        let (column, row) = (index as u32 % RESOLUTION, index as u32 / RESOLUTION);
+                        if column == 0 || row == 0 || column == last || row == last {
+                            let coordinate = UVec2::new(x, y) * last + UVec2::new(column, row);
+                            vertices
+                                .entry(position.map(f32::to_bits))
+                                .or_default()
+                                .push((local_up, Vec3::from(*normal), coordinate));
+                        }
+                    }
+                }
+            }
+        }
+        vertices
+    }
+
+    #[test]
+    fn face_edges_are_shared_by_adjacent_faces() {
+        let vertices = border_vertices(0);
+        // Every one of the 12 cube edges holds its inner vertices once, plus the 8 corners.
+        assert_eq!(vertices.len(), 12 * (RESOLUTION as usize - 2) + 8);
+        let last = RESOLUTION - 1;
+        for (position, faces) in &vertices {
+            let position = position.map(f32::from_bits);
+            let (first_face, first_normal, coordinate) = faces[0];
+            // Cube corners are shared by three faces, the rest of the edges by two.
+            let on_corner = [coordinate.x, coordinate.y]
+                .iter()
+                .all(|&component| component == 0 || component == last);
+            let expected_faces = if on_corner { 3 } else { 2 };
+            assert_eq!(
+                faces.len(),
+                expected_faces,
+                "{position:?} at {coordinate} on face {first_face} is not shared"
+            );
+            for &(face, normal, _) in &faces[1..] {
+                assert_ne!(face, first_face, "{position:?} appears twice on one face");
+                assert_eq!(
+                    normal, first_normal,
+                    "normals differ at {position:?} between faces {first_face} and {face}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn chunk_edges_match_their_neighbours() {
+        for depth in 1..=2 {
+            for (position, chunks) in border_vertices(depth) {
+                let position = position.map(f32::from_bits);
+                assert!(
+                    chunks.len() >= 2,
+                    "{position:?} at depth {depth} is not shared"
+                );
+                let (_, first_normal, _) = chunks[0];
+                for &(_, normal, _) in &chunks[1..] {
+                    assert_eq!(normal, first_normal, "normals differ at {position:?}");
+                }
+            }
+        }
     }
 }
@@ -0,0 +1,231 @@
+//! The clock that drives the ecological simulation, and the controls to pause or speed it up.
+
+use bevy::prelude::*;
+use common::{schedules::Simulation, states::Screen, system_sets::SimulationSet};
+use leafwing_input_manager::prelude::*;
+
+/// Real-time seconds per simulation tick at normal speed.
+const SECONDS_PER_TICK: f32 = 0.25;
+pub const DAYS_PER_YEAR: u64 = 360;
+
+pub fn plugin(app: &mut App) {
+    app.register_type::<SimulationClock>()
+        .add_plugins((
+            schedule_plugin,
+            InputManagerPlugin::<SimulationActions>::default(),
+        ))
+        .add_systems(OnEnter(Screen::Gameplay), (reset_clock, setup))
+        .add_systems(
+            Update,
+            record_speed_changes.run_if(in_state(Screen::Gameplay)),
+        )
+        .add_systems(
+            FixedUpdate,
+            run_simulation.run_if(in_state(Screen::Gameplay)),
+        );
+}
+
+/// Sets up the [`Simulation`] schedule itself, without any input or UI.
+fn schedule_plugin(app: &mut App) {
+    app.init_resource::<SimulationClock>()
+        .init_schedule(Simulation)
+        // Order new `SimulationSet` variants by adding them here:
+        .configure_sets(
+            Simulation,
+            (
+                SimulationSet::Clock,
+                SimulationSet::Environment,
+                SimulationSet::Ecology,
+                SimulationSet::Facilities,
+            )
+                .chain(),
+        )
+        .add_systems(Simulation, advance_clock.in_set(SimulationSet::Clock));
+}
+
+/// How fast simulation time passes compared to real time.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Reflect)]
+pub enum SimulationSpeed {
+    #[default]
+    Normal,
+    Double,
+    Fast,
+}
+
+impl SimulationSpeed {
+    pub fn multiplier(self) -> f32 {
+        match self {
+            SimulationSpeed::Normal => 1.0,
+            SimulationSpeed::Double => 2.0,
+            SimulationSpeed::Fast => 5.0,
+        }
+    }
+}
+
+/// Keeps track of simulated time. One tick of the [`Simulation`] schedule is one day.
+#[derive(Default, Reflect, Resource)]
+#[reflect(Resource)]
+pub struct SimulationClock {
+    pub paused: bool,
+    pub speed: SimulationSpeed,
+    /// Number of ticks the simulation has run for.
+    pub ticks: u64,
+    /// Real time that has not been turned into a tick yet.
+    accumulated: f32,
+}
+
+impl SimulationClock {
+    /// Days since the start of the simulation.
+    pub fn days(&self) -> u64 {
+        self.ticks
+    }
+
+    /// The current day within the year, starting at zero.
+    pub fn day_of_year(&self) -> u64 {
+        self.days() % DAYS_PER_YEAR
+    }
+
+    /// The current year, starting at zero.
+    pub fn year(&self) -> u64 {
+        self.days() / DAYS_PER_YEAR
+    }
+
+    /// How far the year has progressed, between `0.0` and `1.0`.
+    pub fn year_fraction(&self) -> f32 {
+        self.day_of_year() as f32 / DAYS_PER_YEAR as f32
+    }
+}
+
+#[derive(Actionlike, Clone, Debug, Eq, Hash, PartialEq, Reflect)]
+enum SimulationActions {
+    TogglePause,
+    NormalSpeed,
+    DoubleSpeed,
+    FastSpeed,
+}
+
+fn setup(mut commands: Commands) {
+    let input_map = InputMap::new([
+        (SimulationActions::TogglePause, KeyCode::KeyP),
+        (SimulationActions::NormalSpeed, KeyCode::Digit1),
+        (SimulationActions::DoubleSpeed, KeyCode::Digit2),
+        (SimulationActions::FastSpeed, KeyCode::Digit3),
+    ]);
+    commands.spawn((
+        Name::new("SimulationControls"),
+        InputManagerBundle::with_map(input_map),
+        StateScoped(Screen::Gameplay),
+    ));
+}
+
+fn reset_clock(mut clock: ResMut<SimulationClock>) {
+    *clock = SimulationClock::default();
+}
+
+fn record_speed_changes(
+    mut clock: ResMut<SimulationClock>,
+    action_state: Single<&ActionState<SimulationActions>>,
+) {
+    let action_state = action_state.into_inner();
+    if action_state.just_pressed(&SimulationActions::TogglePause) {
+        clock.paused = !clock.paused;
+    }
+    for (action, speed) in [
+        (SimulationActions::NormalSpeed, SimulationSpeed::Normal),
+        (SimulationActions::DoubleSpeed, SimulationSpeed::Double),
+        (SimulationActions::FastSpeed, SimulationSpeed::Fast),
+    ] {
+        if action_state.just_pressed(&action) {
+            clock.speed = speed;
+            clock.paused = false;
+        }
+    }
+}
+
+/// Runs the [`Simulation`] schedule as many times as the elapsed fixed time and the
+/// current speed call for.
+fn run_simulation(world: &mut World) {
+    let delta = world.resource::<Time<Fixed>>().delta_secs();
+    let ticks = {
+        let mut clock = world.resource_mut::<SimulationClock>();
+        if clock.paused {
+            return;
+        }
+        clock.accumulated += delta * clock.speed.multiplier();
+        let ticks = (clock.accumulated / SECONDS_PER_TICK).floor();
+        clock.accumulated -= ticks * SECONDS_PER_TICK;
+        ticks as u32
+    };
+    for _ in 0..ticks {
+        world.run_schedule(Simulation);
+    }
+}
+
+fn advance_clock(mut clock: ResMut<SimulationClock>) {
+    clock.ticks += 1;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The sets that ran, in order, along with the clock ticks each of them saw.
+    #[derive(Default, Resource)]
+    struct RunLog(Vec<(u64, SimulationSet)>);
+
+    fn log_set(set: SimulationSet) -> impl Fn(Res<SimulationClock>, ResMut<RunLog>) {
+        move |clock, mut log| log.0.push((clock.ticks, set))
+    }
+
+    #[test]
+    fn each_tick_advances_the_clock_then_runs_the_sets_in_order() {
+        let mut app = App::new();
+        app.add_plugins(schedule_plugin).init_resource::<RunLog>();
+        // Added in reverse so that only the configured order can line them up.
+        app.add_systems(
+            Simulation,
+            (
+                log_set(SimulationSet::Facilities).in_set(SimulationSet::Facilities),
+                log_set(SimulationSet::Ecology).in_set(SimulationSet::Ecology),
+                log_set(SimulationSet::Environment).in_set(SimulationSet::Environment),
+                log_set(SimulationSet::Clock)
+                    .in_set(SimulationSet::Clock)
+                    .after(advance_clock),
+            ),
+        );
+
+        const TICKS: u64 = 3;
+        for _ in 0..TICKS {
+            app.world_mut().run_schedule(Simulation);
+        }
+
+        let expected: Vec<_> = (1..=TICKS)
+            .flat_map(|tick| {
+                [
+                    SimulationSet::Clock,
+                    SimulationSet::Environment,
+                    SimulationSet::Ecology,
+                    SimulationSet::Facilities,
+                ]
+                .map(|set| (tick, set))
+            })
+            .collect();
+        assert_eq!(app.world().resource::<RunLog>().0, expected);
+        let clock = app.world().resource::<SimulationClock>();
+        assert_eq!(clock.ticks, TICKS);
+        assert_eq!(clock.days(), TICKS);
+        assert_eq!(clock.year(), 0);
+    }
+
+    #[test]
+    fn clock_rolls_over_into_the_next_year() {
+        let mut app = App::new();
+        app.add_plugins(schedule_plugin);
+        for _ in 0..DAYS_PER_YEAR + 5 {
+            app.world_mut().run_schedule(Simulation);
+        }
+        let clock = app.world().resource::<SimulationClock>();
+        assert_eq!(clock.year(), 1);
+        assert_eq!(clock.day_of_year(), 5);
+    }
+}
@@ -1,14 +1,43 @@
 //! The title screen that appears when the game starts.
 
+use std::hash::{BuildHasher, RandomState};
+
 use bevy::prelude::*;
 use common::states::Screen;
+use planet_generation::PlanetSeed;
 use theme::widgets::{Containers, Widgets};
 use tutorial::Tutorial;
 
 pub fn plugin(app: &mut App) {
-    app.add_systems(OnEnter(Screen::Title), spawn_title_screen);
+    app.add_systems(OnEnter(Screen::Title), spawn_title_screen)
+        .add_systems(
+            Update,
+            (enter_seed, show_seed)
+                .chain()
+                .run_if(in_state(Screen::Title)),
+        );
 }
 
+/// The largest number of digits a seed can be entered with.
+const MAX_SEED_DIGITS: u32 = 9;
+
+const DIGIT_KEYS: [(KeyCode, KeyCode); 10] = [
+    (KeyCode::Digit0, KeyCode::Numpad0),
+    (KeyCode::Digit1, KeyCode::Numpad1),
+    (KeyCode::Digit2, KeyCode::Numpad2),
+    (KeyCode::Digit3, KeyCode::Numpad3),
+    (KeyCode::Digit4, KeyCode::Numpad4),
+    (KeyCode::Digit5, KeyCode::Numpad5),
+    (KeyCode::Digit6, KeyCode::Numpad6),
+    (KeyCode::Digit7, KeyCode::Numpad7),
+    (KeyCode::Digit8, KeyCode::Numpad8),
+    (KeyCode::Digit9, KeyCode::Numpad9),
+];
+
+/// Shows the seed the next planet will be generated from.
+#[derive(Component)]
+struct SeedLabel;
+
 fn spawn_title_screen(mut commands: Commands) {
     commands
         .ui_root()
@@ -16,6 +45,8 @@ fn spawn_title_screen(mut commands: Commands) {
         .with_children(|parent| {
             parent.button("Tutorial").observe(enter_tutorial_screen);
             parent.button("Start Game").observe(enter_gameplay_screen);
+            parent.label("").insert(SeedLabel);
+            parent.button("Random Seed").observe(randomize_seed);
             parent.button("Credits").observe(enter_credits_screen);
 
             #[cfg(not(target_family = "wasm"))]
@@ -27,6 +58,37 @@ fn enter_gameplay_screen(_: Trigger<Pointer<Pressed>>, mut next_screen: ResMut<N
     next_screen.set(Screen::Gameplay);
 }
 
+/// Typing digits on the title screen appends them to the seed, backspace removes the last one.
+fn enter_seed(input: Res<ButtonInput<KeyCode>>, mut seed: ResMut<PlanetSeed>) {
+    if input.just_pressed(KeyCode::Backspace) {
+        seed.0 /= 10;
+    }
+    for (digit, (key, numpad_key)) in (0..).zip(DIGIT_KEYS) {
+        if input.any_just_pressed([key, numpad_key]) && seed.0 < 10u32.pow(MAX_SEED_DIGITS - 1) {
+            seed.0 = seed.0 * 10 + digit;
+        }
+    }
+}
+
+fn show_seed(seed: Res<PlanetSeed>, mut labels: Query<&mut Text, With<SeedLabel>>) {
+    for mut text in &mut labels {
+        // Also fill in labels that were just spawned.
+        if seed.is_changed() || text.0.is_empty() {
+            text.0 = format!("Seed: {} (type to change)", seed.0);
+        }
+    }
+}
+
+fn randomize_seed(
+    _: Trigger<Pointer<Pressed>>,
+    time: Res<Time<Real>>,
+    mut seed: ResMut<PlanetSeed>,
+) {
+    // The time makes the seed differ even where `RandomState` has no source of randomness.
+    let entropy = RandomState::new().hash_one(time.elapsed().as_nanos());
+    *seed = PlanetSeed::from_entropy(entropy);
+}
+
 fn enter_tutorial_screen(
     _: Trigger<Pointer<Pressed>>,
     mut next_screen: ResMut<NextState<Screen>>,
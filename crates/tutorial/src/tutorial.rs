@@ -5,7 +5,7 @@ use bevy_yarnspinner::prelude::{YarnProject, YarnSpinnerPlugin};
 use bevy_yarnspinner_example_dialogue_view::ExampleYarnSpinnerDialogueViewPlugin;
 use camera::CameraMovementIntentions;
 use common::states::Screen;
-use planet_generation::PlanetState;
+use planet_generation::{GEOTHERMAL_OVERLAY, PlanetState};
 
 use crate::input_observer::*;
 
@@ -86,7 +86,7 @@ fn observe_input(
             }
         }
         InputObserver::Overlay(overlay) => {
-            if single.into_inner().geothermal_overlay {
+            if single.into_inner().overlay == Some(GEOTHERMAL_OVERLAY) {
                 overlay.set_done();
             }
         }
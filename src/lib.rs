@@ -47,10 +47,16 @@ impl Plugin for AppPlugin {
         app.add_plugins((
             asset_tracking::plugin,
             CameraPlugin(CameraSettings {
-                zoom_speed: 10.,
-                zoom_min: 1.,
+                zoom_step: 0.5,
+                zoom_speed: 4.,
+                min_altitude: 0.05,
                 zoom_max: 100.,
-                pan_speed: 5.,
+                drag_speed: 0.1,
+                pan_speed: 60.,
+                easing: 8.,
+                focus_altitude: 1.,
+                max_tilt: 60.,
+                tilt_altitude: 2.,
             }),
             screens::plugin,
             theme::plugin,